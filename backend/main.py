@@ -2,7 +2,7 @@ import os
 from fastapi import FastAPI, File, UploadFile, Form, HTTPException
 from fastapi.responses import JSONResponse
 from pydantic import BaseModel
-from typing import List
+from typing import List, Optional
 from dotenv import load_dotenv
 import httpx # Added for async requests
 import json
@@ -51,6 +51,47 @@ with open("programs/idl/rental_flow.json") as f:
 # Devnet USDC mint address
 USDC_MINT_PUBKEY = PublicKey.from_string("4zMMC9srt5Ri5X14GAgXhaHii3GnPAEERYPJgZJDncDU") # Renamed for clarity
 
+# Namespace of the marketplace's item registry (seeds ["item_registry", namespace])
+MARKETPLACE_NAMESPACE = os.getenv("MARKETPLACE_NAMESPACE", "main")
+
+# Item categories whose owners must hold an identity attestation (vehicles, power tools)
+GATED_CATEGORIES = {3, 4}
+
+# PDA helpers, matching the seeds in programs/item_registration.rs and programs/rental_flow.rs
+def find_item_registry_pda() -> PublicKey:
+    return PublicKey.find_program_address(
+        [b"item_registry", MARKETPLACE_NAMESPACE.encode()], ITEM_REGISTRATION_PROGRAM_ID
+    )[0]
+
+def find_item_account_pda(item_registry: PublicKey, item_id: int) -> PublicKey:
+    return PublicKey.find_program_address(
+        [b"item", bytes(item_registry), item_id.to_bytes(8, "little")], ITEM_REGISTRATION_PROGRAM_ID
+    )[0]
+
+def find_attestation_pda(wallet: PublicKey) -> PublicKey:
+    return PublicKey.find_program_address([b"attestation", bytes(wallet)], ITEM_REGISTRATION_PROGRAM_ID)[0]
+
+def find_user_profile_pda(wallet: PublicKey) -> PublicKey:
+    return PublicKey.find_program_address([b"user_profile", bytes(wallet)], ITEM_REGISTRATION_PROGRAM_ID)[0]
+
+def find_rental_transaction_pda(item_account: PublicKey, renter: PublicKey, nonce: int) -> PublicKey:
+    return PublicKey.find_program_address(
+        [b"rental_transaction", bytes(item_account), bytes(renter), nonce.to_bytes(8, "little")],
+        RENTAL_PROGRAM_ID
+    )[0]
+
+def find_booking_schedule_pda(item_account: PublicKey) -> PublicKey:
+    return PublicKey.find_program_address([b"booking_schedule", bytes(item_account)], RENTAL_PROGRAM_ID)[0]
+
+def find_platform_config_pda(item_registry: PublicKey) -> PublicKey:
+    return PublicKey.find_program_address([b"platform_config", bytes(item_registry)], RENTAL_PROGRAM_ID)[0]
+
+def find_hold_pda(item_account: PublicKey) -> PublicKey:
+    return PublicKey.find_program_address([b"hold", bytes(item_account)], RENTAL_PROGRAM_ID)[0]
+
+def find_rental_authority_pda() -> PublicKey:
+    return PublicKey.find_program_address([b"rental_authority"], RENTAL_PROGRAM_ID)[0]
+
 # Pydantic models for responses
 class ItemAccountResponse(BaseModel):
     owner: str
@@ -69,8 +110,10 @@ class RentalTransactionResponse(BaseModel):
     start_time: int
     end_time: int
     total_price: int
-    is_active: bool
-    is_completed: bool
+    security_deposit: int
+    payment_mint: str
+    nonce: int
+    status: str # RentalStatus variant, e.g. "Requested", "Active", "Completed"
 
 app = FastAPI()
 
@@ -115,21 +158,15 @@ async def get_item(item_account_key_str: str):
 
 @app.get("/items", response_model=List[int]) # Initially returning List[int] for item_ids
 async def get_all_items():
-    # TODO: Enhance to return List[ItemAccountResponse] if ItemAccount PDAs can be derived
-    # solely from item_id or if ItemRegistry stores ItemAccount pubkeys.
-    # Current ItemRegistry only stores item_ids (Vec<u64>).
-    # Deriving ItemAccount PDA typically requires [b"item_account", owner.key().as_ref(), &item_id.to_le_bytes()].
-    # The 'owner' is not available in ItemRegistry alongside item_id.
-    # If ItemAccount PDA was, for example, [b"item", &item_id.to_le_bytes()], then we could fetch.
+    # TODO: Enhance to return List[ItemAccountResponse]: item accounts can be derived from the
+    # registry and each item_id with find_item_account_pda.
     try:
         async with AsyncClient(QUICKNODE_URL) as connection:
             dummy_wallet = Wallet.local()
             provider = Provider(connection, dummy_wallet)
             item_registration_program = await Program.create(idl, ITEM_REGISTRATION_PROGRAM_ID, provider)
 
-            item_registry_pda, _ = PublicKey.find_program_address(
-                [b"item_registry"], ITEM_REGISTRATION_PROGRAM_ID
-            )
+            item_registry_pda = find_item_registry_pda()
             
             item_registry_data = await item_registration_program.account["ItemRegistry"].fetch(item_registry_pda)
             
@@ -163,8 +200,10 @@ async def get_rental_transaction(rental_transaction_key_str: str):
             start_time=rental_data.start_time,
             end_time=rental_data.end_time,
             total_price=rental_data.total_price,
-            is_active=rental_data.is_active,
-            is_completed=rental_data.is_completed
+            security_deposit=rental_data.security_deposit,
+            payment_mint=str(rental_data.payment_mint),
+            nonce=rental_data.nonce,
+            status=type(rental_data.status).__name__
         )
     except AccountDoesNotExistError:
         # connection is managed by async with
@@ -217,6 +256,13 @@ class RegisterItemRequest(BaseModel):
     price_per_day: int
     metadata_uri: str
     user_pubkey: str  # base58 string
+    category: int = 0 # One of the CATEGORY_* values of item_registration
+    requires_verified_renter: bool = False
+    expires_at: Optional[int] = None # Unix timestamp; None never expires
+    security_deposit: int = 0 # Base units of the price mint
+    booking_mode: str = "Instant" # Or "RequestToBook"
+    cancellation_policy: str = "Flexible" # Or "Moderate", "Strict"
+    price_mint: str = str(USDC_MINT_PUBKEY) # Mint the prices are denominated in, base58 string
 
 @app.post("/build-register-item-tx/")
 async def build_register_item_tx(req: RegisterItemRequest):
@@ -227,16 +273,17 @@ async def build_register_item_tx(req: RegisterItemRequest):
             dummy_wallet = Wallet.local()  # Not used for signing, just for Anchor context
             provider = Provider(connection, dummy_wallet)
             program = await Program.create(idl, ITEM_REGISTRATION_PROGRAM_ID, provider)
+            rental_program = await Program.create(rental_idl, RENTAL_PROGRAM_ID, provider)
 
             user_pubkey = PublicKey(req.user_pubkey)
 
-            # Derive PDAs (item_registry)
-            item_registry_pda, _ = PublicKey.find_program_address(
-                [b"item_registry"], program.program_id
-            )
-            # For item_account, generate a random keypair (frontend should use the same logic)
-            from solders.keypair import Keypair
-            item_account = Keypair()
+            # Derive PDAs (item_registry, and the owner's attestation for gated categories)
+            item_registry_pda = find_item_registry_pda()
+            attestation_pda = find_attestation_pda(user_pubkey) if req.category in GATED_CATEGORIES else None
+            # The item account is the PDA of the ID the item is about to be assigned. Concurrent
+            # registrations in the same registry race for it; the loser must rebuild the transaction.
+            item_registry_data = await program.account["ItemRegistry"].fetch(item_registry_pda)
+            item_account = find_item_account_pda(item_registry_pda, item_registry_data.next_item_id + 1)
 
             # Build the transaction
             tx = Transaction()
@@ -247,10 +294,29 @@ async def build_register_item_tx(req: RegisterItemRequest):
                     req.price_per_hour,
                     req.price_per_day,
                     req.metadata_uri,
+                    req.category,
+                    req.requires_verified_renter,
+                    req.expires_at,
+                    req.security_deposit,
+                    getattr(program.type["BookingMode"], req.booking_mode)(),
+                    getattr(program.type["CancellationPolicy"], req.cancellation_policy)(),
                     accounts={ # Matches RegisterItem Accounts struct
                         "owner": user_pubkey,
-                        "item_account": item_account.public_key,
+                        "item_account": item_account,
                         "item_registry": item_registry_pda,
+                        "price_mint": PublicKey.from_string(req.price_mint),
+                        "attestation": attestation_pda, # Optional account
+                        "system_program": SYSTEM_PROGRAM_ID,
+                    }
+                )
+            )
+            # Open the item's booking schedule right away, so the item can be quoted before its first booking
+            tx.add(
+                await rental_program.instruction["open_booking_schedule"](
+                    accounts={ # Matches OpenBookingSchedule Accounts struct
+                        "payer": user_pubkey,
+                        "item_account": item_account,
+                        "booking_schedule": find_booking_schedule_pda(item_account),
                         "system_program": SYSTEM_PROGRAM_ID,
                     }
                 )
@@ -263,18 +329,18 @@ async def build_register_item_tx(req: RegisterItemRequest):
             # Serialize transaction using serialize_message
             serialized_tx = base64.b64encode(tx.serialize_message()).decode('ascii')
 
-            return {"transaction": serialized_tx, "item_account": str(item_account.public_key)}
+            return {"transaction": serialized_tx, "item_account": str(item_account)}
     except Exception as e:
         # connection is managed by async with
         raise HTTPException(status_code=500, detail=str(e))
 
 class InitiateRentalRequest(BaseModel):
     item_account: str # Pubkey string
-    renter: str       # Pubkey string (fee payer for this tx)
-    owner: str        # Pubkey string (owner of the item)
-    renter_usdc: str  # Pubkey string (renter's USDC ATA)
-    hours: int
+    renter: str       # Pubkey string (fee payer and only signer of this tx)
+    renter_usdc: str  # Pubkey string (renter's token account of the item's price mint)
+    duration: int     # Booking units: hours, or slots for slot-priced items
     start_time: int   # Unix timestamp
+    promo: Optional[str] = None # Pubkey string of a Promo account to redeem
     # Removed program ID fields, they are constants or derived in backend
     # escrow_usdc: str = None # Derived in backend
 
@@ -285,38 +351,51 @@ async def build_initiate_rental_tx(req: InitiateRentalRequest):
             dummy_wallet = Wallet.local()
             provider = Provider(connection, dummy_wallet)
             program = await Program.create(rental_idl, RENTAL_PROGRAM_ID, provider)
+            item_reg_program_client = await Program.create(idl, ITEM_REGISTRATION_PROGRAM_ID, provider)
 
             # Convert all provided keys to PublicKey
             item_account_pk = PublicKey.from_string(req.item_account)
             renter_pk = PublicKey.from_string(req.renter)
-            owner_pk = PublicKey.from_string(req.owner)
             renter_usdc_pk = PublicKey.from_string(req.renter_usdc)
 
-            # Derive rental_transaction_pda
-            rental_transaction_pda, _ = PublicKey.find_program_address(
-                [b"rental_transaction", item_account_pk.to_bytes(), renter_pk.to_bytes()],
-                program.program_id  # RENTAL_PROGRAM_ID
+            try:
+                item_account_data = await item_reg_program_client.account["ItemAccount"].fetch(item_account_pk)
+            except AccountDoesNotExistError:
+                raise HTTPException(status_code=404, detail=f"ItemAccount not found: {item_account_pk}")
+
+            # Derive rental_transaction_pda from the item's next rental nonce. If another booking of
+            # the item lands first, the nonce moves on and the transaction must be rebuilt.
+            rental_transaction_pda = find_rental_transaction_pda(
+                item_account_pk, renter_pk, item_account_data.next_rental_nonce
             )
 
-            # Derive escrow_usdc ATA
+            # Derive escrow ATA of the payment mint, which must be the item's price mint
             escrow_usdc_pubkey = get_associated_token_address(
                 owner=rental_transaction_pda,
-                mint=USDC_MINT_PUBKEY
+                mint=item_account_data.price_mint
             )
 
             tx = Transaction()
             tx.add(
                 await program.instruction["initiate_rental"](
-                    req.hours,
+                    req.duration,
                     req.start_time,
                     accounts={ # Matches InitiateRental Accounts struct
                         "renter": renter_pk,
-                        "owner": owner_pk, # Owner of item_account, needed for CPI to item_registration
+                        "owner": item_account_data.owner, # Recorded on the rental; does not sign
                         "item_account": item_account_pk,
                         "rental_transaction": rental_transaction_pda,
-                        "usdc_mint": USDC_MINT_PUBKEY,
+                        "booking_schedule": find_booking_schedule_pda(item_account_pk),
+                        "usdc_mint": item_account_data.price_mint,
                         "renter_usdc": renter_usdc_pk,
                         "escrow_usdc": escrow_usdc_pubkey,
+                        "item_registry": item_account_data.registry,
+                        "platform_config": find_platform_config_pda(item_account_data.registry),
+                        "rental_authority": find_rental_authority_pda(),
+                        # Optional accounts: the renter's attestation and a promo to redeem
+                        "attestation": find_attestation_pda(renter_pk) if item_account_data.requires_verified_renter else None,
+                        "promo": PublicKey.from_string(req.promo) if req.promo else None,
+                        "hold": find_hold_pda(item_account_pk),
                         "item_registration_program": ITEM_REGISTRATION_PROGRAM_ID, # Program, not an account key
                         "token_program": TOKEN_PROGRAM_ID,
                         "system_program": SYSTEM_PROGRAM_ID,
@@ -331,7 +410,13 @@ async def build_initiate_rental_tx(req: InitiateRentalRequest):
             
             serialized_tx = base64.b64encode(tx.serialize_message()).decode('ascii')
             
-            return {"transaction": serialized_tx, "escrow_usdc": str(escrow_usdc_pubkey)}
+            return {
+                "transaction": serialized_tx,
+                "rental_transaction": str(rental_transaction_pda),
+                "escrow_usdc": str(escrow_usdc_pubkey)
+            }
+    except HTTPException as he:
+        raise he
     except Exception as e:
         # connection is managed by async with
         raise HTTPException(status_code=500, detail=str(e))
@@ -340,6 +425,7 @@ class CompleteRentalRequest(BaseModel):
     owner_key_str: str # Renamed from owner for clarity, as it's the requestor's key
     item_account_key_str: str # Renamed from item_account
     rental_transaction_key_str: str  # Renamed from rental_transaction, PDA provided by frontend
+    returned_at: int # Unix timestamp the owner confirms the item was returned at; late fees run up to it
     # owner_usdc_ata_str: str # Removed, will be derived
     # system_usdc_ata_str: str # Removed, will be derived
     # associated_token_program_id_str: str # Removed, will use constant
@@ -351,8 +437,8 @@ class CancelRentalRequest(BaseModel):
     rental_transaction_key_str: str
     renter_key_str: str
     item_account_key_str: str
-    owner_key_str: str # Owner of the item, required for CPI to item_registration
     fee_payer_key_str: str # Who pays for this transaction (can be renter or owner)
+    owner_cosigns: bool = False # The item's owner co-signs to refund the renter in full
 
 # ITEM_REGISTRATION_PROGRAM_ID and RENTAL_PROGRAM_ID moved to top
 
@@ -385,25 +471,36 @@ async def build_complete_rental_tx(req: CompleteRentalRequest):
                     request_owner_pubkey == rental_transaction_data.owner):
                 raise HTTPException(status_code=403, detail="Unauthorized: Provided owner does not match record owner.")
 
-            owner_usdc_ata = get_associated_token_address(owner=request_owner_pubkey, mint=USDC_MINT_PUBKEY)
-            system_usdc_ata = get_associated_token_address(owner=SYSTEM_REVENUE_ADDRESS, mint=USDC_MINT_PUBKEY)
+            # Token accounts of the mint held in escrow
+            payment_mint = rental_transaction_data.payment_mint
+            owner_usdc_ata = get_associated_token_address(owner=request_owner_pubkey, mint=payment_mint)
+            system_usdc_ata = get_associated_token_address(owner=SYSTEM_REVENUE_ADDRESS, mint=payment_mint)
             renter_pubkey = rental_transaction_data.renter
+            renter_usdc_ata = get_associated_token_address(owner=renter_pubkey, mint=payment_mint)
             escrow_usdc_pubkey = get_associated_token_address(
                 owner=rental_transaction_pubkey,
-                mint=USDC_MINT_PUBKEY
+                mint=payment_mint
             )
 
             tx = Transaction()
             tx.add(
                 await rental_program.instruction["complete_rental"](
+                    req.returned_at,
                     accounts={ # Matches CompleteRental Accounts struct
                         "owner": request_owner_pubkey,
                         "rental_transaction": rental_transaction_pubkey,
                         "item_account": item_account_pubkey,
-                        "usdc_mint": USDC_MINT_PUBKEY,
+                        "booking_schedule": find_booking_schedule_pda(item_account_pubkey),
+                        "usdc_mint": payment_mint,
                         "owner_usdc": owner_usdc_ata,
                         "system_usdc": system_usdc_ata,
+                        "renter_usdc": renter_usdc_ata, # Receives the security deposit back
                         "escrow_usdc": escrow_usdc_pubkey,
+                        "item_registry": item_account_data.registry,
+                        "rental_authority": find_rental_authority_pda(),
+                        # UserProfile PDAs, whose rental counters are incremented if they exist
+                        "owner_profile": find_user_profile_pda(rental_transaction_data.owner),
+                        "renter_profile": find_user_profile_pda(renter_pubkey),
                         "item_registration_program": ITEM_REGISTRATION_PROGRAM_ID,
                         "token_program": TOKEN_PROGRAM_ID,
                         # system_program and associated_token_program are not in Rust struct
@@ -443,7 +540,6 @@ async def build_cancel_rental_tx(req: CancelRentalRequest):
             rental_transaction_pubkey = PublicKey.from_string(req.rental_transaction_key_str)
             renter_pubkey = PublicKey.from_string(req.renter_key_str)
             item_account_pubkey = PublicKey.from_string(req.item_account_key_str)
-            owner_pubkey = PublicKey.from_string(req.owner_key_str) # Owner of item, for CPI
             fee_payer_pubkey = PublicKey.from_string(req.fee_payer_key_str)
 
             # Authorization Checks
@@ -457,32 +553,34 @@ async def build_cancel_rental_tx(req: CancelRentalRequest):
             except AccountDoesNotExistError:
                 raise HTTPException(status_code=404, detail=f"ItemAccount not found: {item_account_pubkey}")
 
-            # Verify renter (signer of cancel can be renter or owner, but renter in PDA must match)
+            # The renter always signs; the rental PDA is seeded with their key
             if renter_pubkey != rental_transaction_data.renter:
-                 # This check might need refinement based on who is allowed to call cancel.
-                 # If owner can cancel, this check is too strict.
-                 # Assuming for now the renter_key_str in request is the actual renter.
-                pass # Allowing owner to cancel too, as long as they are the item owner.
-
-            # Verify owner of the item matches
-            if owner_pubkey != item_account_data.owner:
-                raise HTTPException(status_code=403, detail="Unauthorized: Provided owner does not match item's owner.")
-            # Verify owner in rental transaction also matches (consistency)
-            if owner_pubkey != rental_transaction_data.owner:
-                 raise HTTPException(status_code=403, detail="Unauthorized: Provided owner does not match rental transaction's owner.")
-
-
-            renter_usdc_ata = get_associated_token_address(owner=rental_transaction_data.renter, mint=USDC_MINT_PUBKEY) # renter from rental_transaction_data
-            escrow_usdc_pubkey = get_associated_token_address(owner=rental_transaction_pubkey, mint=USDC_MINT_PUBKEY)
+                raise HTTPException(status_code=403, detail="Unauthorized: Provided renter does not match rental transaction's renter.")
+            if rental_transaction_data.item != item_account_pubkey:
+                raise HTTPException(status_code=400, detail="Rental transaction does not belong to the provided item.")
+
+            # Token accounts of the mint held in escrow, receiving the renter's, owner's and platform's shares
+            payment_mint = rental_transaction_data.payment_mint
+            owner_pubkey = rental_transaction_data.owner
+            renter_usdc_ata = get_associated_token_address(owner=renter_pubkey, mint=payment_mint)
+            owner_usdc_ata = get_associated_token_address(owner=owner_pubkey, mint=payment_mint)
+            system_usdc_ata = get_associated_token_address(owner=SYSTEM_REVENUE_ADDRESS, mint=payment_mint)
+            escrow_usdc_pubkey = get_associated_token_address(owner=rental_transaction_pubkey, mint=payment_mint)
 
             accounts_dict = { # Matches CancelRental Accounts struct
-                "renter": rental_transaction_data.renter, # Renter from the transaction state
-                "owner": owner_pubkey, # Item owner, for CPI
+                "renter": renter_pubkey,
+                # Optional: the owner co-signs to waive the cancellation policy
+                "owner": owner_pubkey if req.owner_cosigns else None,
                 "rental_transaction": rental_transaction_pubkey,
                 "item_account": item_account_pubkey,
-                "usdc_mint": USDC_MINT_PUBKEY,
-                "renter_usdc": renter_usdc_ata,
+                "booking_schedule": find_booking_schedule_pda(item_account_pubkey),
+                "promo": rental_transaction_data.promo, # Promo redeemed at booking, if any; its use is given back
                 "escrow_usdc": escrow_usdc_pubkey,
+                "renter_usdc": renter_usdc_ata,
+                "owner_usdc": owner_usdc_ata,
+                "system_usdc": system_usdc_ata,
+                "item_registry": item_account_data.registry,
+                "rental_authority": find_rental_authority_pda(),
                 "item_registration_program": ITEM_REGISTRATION_PROGRAM_ID,
                 "token_program": TOKEN_PROGRAM_ID,
                 # system_program and associated_token_program are not in Rust struct
@@ -1,21 +1,121 @@
 use anchor_lang::prelude::*;
+use anchor_lang::system_program::{self, CreateAccount};
+use anchor_spl::token::Mint;
+use pricing::{Adjustment, HourlyRule, Listing};
+use std::str::FromStr;
 
 declare_id!("Dh1N1esPsvQdgakyM13S3CMFzT2jzDeKbNKerx1vK6Jw");
 
+// Constants for validation
+const MAX_NAME_LENGTH: usize = 60;
+const MAX_DESCRIPTION_LENGTH: usize = 250;
+const MAX_REGISTRY_ITEMS: usize = 1000; // Corresponds to ItemRegistry space allocation for item_ids
+const MAX_METADATA_URI_LENGTH: usize = 200; // Max length for metadata URI
+const MAX_NAMESPACE_LENGTH: usize = 32; // Namespaces are PDA seeds, limited to the max seed length
+// A batch must fit in one 1232-byte transaction. The owner's signature, the fixed accounts and
+// the instruction header take ~315 bytes; each entry adds 33 bytes for its item account plus its
+// args (49 bytes + its name, description and metadata URI). 4 entries fit when each entry's text
+// totals at most 147 bytes.
+const MAX_BATCH_SIZE: usize = 4;
+const MAX_TRACKED_MINTS: usize = 8; // Corresponds to ItemRegistry space allocation for mint_volumes
+const MIN_RATING: u8 = 1;
+const MAX_RATING: u8 = 5;
+const MAX_DISPLAY_NAME_LENGTH: usize = 32;
+const MAX_PRICING_RULES: usize = 8; // Corresponds to ItemAccount space allocation for pricing_rules
+const MAX_RULE_ADJUSTMENT_BPS: i16 = 10_000; // Percentage rules range from -100% to +100%
+const MAX_AVATAR_URI_LENGTH: usize = 200;
+const MINUTES_PER_HOUR: u16 = 60; // Booking slots must divide the hour evenly
+const PRICE_BOUND_DECIMALS: u32 = 2; // Registry price bounds are in hundredths of a whole token
+
+// Cancellation policy refunds, by notice given before the rental starts
+pub const FULL_REFUND_BPS: u16 = 10_000;
+const PARTIAL_REFUND_BPS: u16 = 5_000;
+const MODERATE_FULL_REFUND_NOTICE_SECONDS: i64 = 48 * 60 * 60;
+const STRICT_PARTIAL_REFUND_NOTICE_SECONDS: i64 = 7 * 24 * 60 * 60;
+
+// Contact preference flags stored in UserProfile::contact_preferences
+pub const CONTACT_IN_APP_CHAT: u8 = 1 << 0;
+pub const CONTACT_EMAIL: u8 = 1 << 1;
+pub const CONTACT_PHONE: u8 = 1 << 2;
+const CONTACT_PREFERENCE_MASK: u8 = CONTACT_IN_APP_CHAT | CONTACT_EMAIL | CONTACT_PHONE;
+
+pub const USER_PROFILE_SEED: &[u8] = b"user_profile";
+
+// Item categories
+pub const CATEGORY_GENERAL: u8 = 0;
+pub const CATEGORY_ELECTRONICS: u8 = 1;
+pub const CATEGORY_OUTDOOR: u8 = 2;
+pub const CATEGORY_VEHICLES: u8 = 3;
+pub const CATEGORY_POWER_TOOLS: u8 = 4;
+const CATEGORY_COUNT: u8 = 5;
+// Regulated categories: listing requires an attestation of the owner's verified identity
+const GATED_CATEGORIES: [u8; 2] = [CATEGORY_VEHICLES, CATEGORY_POWER_TOOLS];
+
+// Trusted identity attestation issuer (replace with your real address)
+const ATTESTATION_ISSUER: &str = "8SbDUtpZQeZRAxu9LV7SR7EaMmXNN4dxcRuGD3KfCv9o";
+pub const ATTESTATION_SEED: &[u8] = b"attestation";
+
+// The rental_flow program signs its CPIs into this program with the PDA derived from
+// RENTAL_AUTHORITY_SEED, so rental-driven updates cannot be forged by other callers.
+const RENTAL_FLOW_PROGRAM_ID: &str = "6XqPznLJiGdqzD4FkD9yQGMN2XQb1fLXL1UKfwq8kgPQ";
+pub const RENTAL_AUTHORITY_SEED: &[u8] = b"rental_authority";
+
+pub const REGISTRY_SEED: &[u8] = b"item_registry";
+// Item accounts are PDAs of their registry and item ID, so registering needs no extra signers
+pub const ITEM_SEED: &[u8] = b"item";
+
 #[program]
 pub mod item_registration {
     use super::*;
 
-    // Constants for validation
-    const MAX_NAME_LENGTH: usize = 60;
-    const MAX_DESCRIPTION_LENGTH: usize = 250;
-    const MAX_REGISTRY_ITEMS: usize = 1000; // Corresponds to ItemRegistry space allocation for item_ids
-    const MAX_METADATA_URI_LENGTH: usize = 200; // Max length for metadata URI
+    // Creates the registry of one marketplace, identified by its namespace (e.g. "sg-central").
+    // Only the program's upgrade authority may call this, so marketplaces (and their namespaces)
+    // are created by the platform.
+    pub fn initialize_registry(ctx: Context<InitializeRegistry>, namespace: String) -> Result<()> {
+        if namespace.is_empty() || namespace.len() > MAX_NAMESPACE_LENGTH {
+            return err!(ErrorCode::InvalidNamespace);
+        }
 
-    pub fn initialize_registry(ctx: Context<InitializeRegistry>) -> Result<()> {
         let registry = &mut ctx.accounts.item_registry;
+        registry.namespace = namespace;
+        registry.bump = ctx.bumps.item_registry;
+        registry.authority = ctx.accounts.payer.key(); // The creator administers the registry
         registry.next_item_id = 0; // Initialize the next_item_id
         registry.item_ids = Vec::new();
+        registry.total_listed = 0;
+        registry.currently_available = 0;
+        registry.active_bookings = 0;
+        registry.currently_rented = 0;
+        registry.lifetime_rentals = 0;
+        registry.mint_volumes = Vec::new();
+        registry.hourly_price_bounds = PriceBounds::UNBOUNDED;
+        registry.daily_price_bounds = PriceBounds::UNBOUNDED;
+        registry.slot_price_bounds = PriceBounds::UNBOUNDED;
+        Ok(())
+    }
+
+    // Sets the price ranges accepted for listings, one per kind of rate since an hour, a day and a
+    // booking slot are priced very differently. Bounds are in hundredths of a whole token of the
+    // item's price mint (e.g. 50 = 0.50 USDC).
+    pub fn set_price_bounds(
+        ctx: Context<SetPriceBounds>,
+        hourly: PriceBounds,
+        daily: PriceBounds,
+        slot: PriceBounds,
+    ) -> Result<()> {
+        for bounds in [&hourly, &daily, &slot] {
+            require!(bounds.min <= bounds.max, ErrorCode::InvalidPriceBounds);
+        }
+        let registry = &mut ctx.accounts.item_registry;
+        registry.hourly_price_bounds = hourly;
+        registry.daily_price_bounds = daily;
+        registry.slot_price_bounds = slot;
+        Ok(())
+    }
+
+    // Retires an empty registry, sending its rent to `recipient`
+    pub fn close_registry(ctx: Context<CloseRegistry>) -> Result<()> {
+        require!(ctx.accounts.item_registry.item_ids.is_empty(), ErrorCode::RegistryNotEmpty);
         Ok(())
     }
 
@@ -26,27 +126,32 @@ pub mod item_registration {
         price_per_hour: u64,
         price_per_day: u64,
         metadata_uri: String,
+        category: u8,
+        requires_verified_renter: bool,
+        expires_at: Option<i64>,
+        security_deposit: u64,
+        booking_mode: BookingMode,
+        cancellation_policy: CancellationPolicy,
     ) -> Result<()> {
+        let args = ItemRegistrationArgs {
+            name,
+            description,
+            price_per_hour,
+            price_per_day,
+            metadata_uri,
+            category,
+            requires_verified_renter,
+            expires_at,
+            security_deposit,
+            booking_mode,
+            cancellation_policy,
+        };
         // Input Validation
-        // Check name length
-        if name.len() > MAX_NAME_LENGTH {
-            return err!(ErrorCode::NameTooLong);
-        }
-        // Check description length
-        if description.len() > MAX_DESCRIPTION_LENGTH {
-            return err!(ErrorCode::DescriptionTooLong);
-        }
-        // Check metadata_uri length
-        if metadata_uri.len() > MAX_METADATA_URI_LENGTH {
-            return err!(ErrorCode::MetadataUriTooLong);
-        }
-        // Check price validity
-        if price_per_hour == 0 || price_per_day == 0 {
-            return err!(ErrorCode::InvalidPrice);
-        }
+        args.validate()?;
+        require_listing_attestation(args.category, &ctx.accounts.owner.key(), ctx.accounts.attestation.as_deref())?;
 
         let item_registry = &mut ctx.accounts.item_registry;
-        let item_account = &mut ctx.accounts.item_account;
+        let registry_key = item_registry.key();
         let owner = ctx.accounts.owner.key();
 
         // Check if registry is full before adding a new item
@@ -54,20 +159,80 @@ pub mod item_registration {
             return err!(ErrorCode::RegistryFull);
         }
 
-        // Increment next_item_id and assign new item_id
-        item_registry.next_item_id += 1;
-        let item_id = item_registry.next_item_id;
-        item_registry.item_ids.push(item_id);
+        // Prices are stored in base units of the price mint
+        let price_mint = &ctx.accounts.price_mint;
+        item_registry.hourly_price_bounds.check(args.price_per_hour, price_mint.decimals)?;
+        item_registry.daily_price_bounds.check(args.price_per_day, price_mint.decimals)?;
+
+        let item_id = item_registry.assign_item_id();
+        item_registry.record_listing_added();
 
         // Store item data
-        item_account.owner = owner;
-        item_account.item_id = item_id;
-        item_account.name = name;
-        item_account.description = description;
-        item_account.price_per_hour = price_per_hour;
-        item_account.price_per_day = price_per_day;
-        item_account.is_available = true;
-        item_account.metadata_uri = metadata_uri;
+        let item = ItemAccount::new(owner, item_id, registry_key, price_mint.key(), price_mint.decimals, args);
+        ctx.accounts.item_account.set_inner(item);
+
+        Ok(())
+    }
+
+    // Registers several items in one instruction. The new item accounts are passed as
+    // remaining accounts (writable item PDAs for the next item IDs), in the same order as `items`.
+    pub fn register_items<'info>(
+        ctx: Context<'_, '_, '_, 'info, RegisterItems<'info>>,
+        items: Vec<ItemRegistrationArgs>,
+    ) -> Result<()> {
+        require!(!items.is_empty() && items.len() <= MAX_BATCH_SIZE, ErrorCode::InvalidBatchSize);
+        require!(ctx.remaining_accounts.len() == items.len(), ErrorCode::BatchAccountMismatch);
+
+        let owner = ctx.accounts.owner.key();
+        let attestation = ctx.accounts.attestation.as_deref();
+        let price_mint = ctx.accounts.price_mint.key();
+        let price_decimals = ctx.accounts.price_mint.decimals;
+
+        // Validate every entry before anything is written, reporting the offending index
+        for (index, args) in items.iter().enumerate() {
+            args.validate()
+                .and_then(|_| require_listing_attestation(args.category, &owner, attestation))
+                .and_then(|_| ctx.accounts.item_registry.hourly_price_bounds.check(args.price_per_hour, price_decimals))
+                .and_then(|_| ctx.accounts.item_registry.daily_price_bounds.check(args.price_per_day, price_decimals))
+                .map_err(|e| {
+                    msg!("Batch entry {} is invalid", index);
+                    e
+                })?;
+        }
+
+        let item_registry = &mut ctx.accounts.item_registry;
+        let registry_key = item_registry.key();
+
+        // Check the whole batch fits in the registry up front
+        if item_registry.item_ids.len() + items.len() > MAX_REGISTRY_ITEMS {
+            return err!(ErrorCode::RegistryFull);
+        }
+
+        for (index, (args, item_info)) in items.into_iter().zip(ctx.remaining_accounts.iter()).enumerate() {
+            let item_id = item_registry.assign_item_id();
+            let item_id_bytes = item_id.to_le_bytes();
+            let (expected_key, bump) =
+                Pubkey::find_program_address(&[ITEM_SEED, registry_key.as_ref(), &item_id_bytes], ctx.program_id);
+            if item_info.key() != expected_key || !item_info.is_writable {
+                msg!("Batch entry {} has an invalid item account", index);
+                return err!(ErrorCode::InvalidItemAccount);
+            }
+
+            // Allocate the item account, owned by this program
+            create_program_account(
+                &ctx.accounts.owner,
+                item_info,
+                &ctx.accounts.system_program,
+                ItemAccount::LEN,
+                &[ITEM_SEED, registry_key.as_ref(), &item_id_bytes, &[bump]],
+            )?;
+
+            item_registry.record_listing_added();
+            let item_account = ItemAccount::new(owner, item_id, registry_key, price_mint, price_decimals, args);
+            let mut data = item_info.try_borrow_mut_data()?;
+            let mut writer: &mut [u8] = &mut data;
+            item_account.try_serialize(&mut writer)?;
+        }
 
         Ok(())
     }
@@ -80,75 +245,307 @@ pub mod item_registration {
         price_per_day: Option<u64>,
         is_available: Option<bool>,
         metadata_uri: Option<String>,
+        requires_verified_renter: Option<bool>,
+        security_deposit: Option<u64>,
+        booking_mode: Option<BookingMode>,
+        cancellation_policy: Option<CancellationPolicy>,
     ) -> Result<()> {
-        let item_account = &mut ctx.accounts.item_account;
+        let args = ItemUpdateArgs {
+            name,
+            description,
+            price_per_hour,
+            price_per_day,
+            is_available,
+            metadata_uri,
+            requires_verified_renter,
+            security_deposit,
+            booking_mode,
+            cancellation_policy,
+        };
+        // Validate all provided fields before updating
+        args.validate()?;
 
-        if let Some(n) = name {
-            // Validate name length before updating
-            if n.len() > MAX_NAME_LENGTH {
-                return err!(ErrorCode::NameTooLong);
-            }
-            item_account.name = n;
+        let item = &mut ctx.accounts.item_account;
+        let was_available = item.is_available;
+        item.apply_update(args);
+        // Switching the price mint re-denominates all of the item's prices
+        if let Some(price_mint) = &ctx.accounts.price_mint {
+            item.price_mint = price_mint.key();
+            item.price_decimals = price_mint.decimals;
         }
-        if let Some(d) = description {
-            // Validate description length before updating
-            if d.len() > MAX_DESCRIPTION_LENGTH {
-                return err!(ErrorCode::DescriptionTooLong);
-            }
-            item_account.description = d;
+        ctx.accounts.item_registry.check_item_prices(item)?;
+        ctx.accounts.item_registry.record_availability_change(was_available, item.is_available);
+        Ok(())
+    }
+
+    // Updates several items owned by the signer. The item accounts are passed as writable
+    // remaining accounts, in the same order as `updates`.
+    pub fn update_items<'info>(
+        ctx: Context<'_, '_, 'info, 'info, UpdateItems<'info>>,
+        updates: Vec<ItemUpdateArgs>,
+    ) -> Result<()> {
+        require!(!updates.is_empty() && updates.len() <= MAX_BATCH_SIZE, ErrorCode::InvalidBatchSize);
+        require!(ctx.remaining_accounts.len() == updates.len(), ErrorCode::BatchAccountMismatch);
+
+        // Validate every entry before anything is written, reporting the offending index
+        for (index, args) in updates.iter().enumerate() {
+            args.validate().map_err(|e| {
+                msg!("Batch entry {} is invalid", index);
+                e
+            })?;
         }
-        if let Some(pph) = price_per_hour {
-            // Validate price before updating
-            if pph == 0 {
-                return err!(ErrorCode::InvalidPrice);
+
+        let owner = ctx.accounts.owner.key();
+        let item_registry = &mut ctx.accounts.item_registry;
+        for (index, (args, item_info)) in updates.into_iter().zip(ctx.remaining_accounts.iter()).enumerate() {
+            if !item_info.is_writable {
+                msg!("Batch entry {} has an invalid item account", index);
+                return err!(ErrorCode::InvalidItemAccount);
             }
-            item_account.price_per_hour = pph;
-        }
-        if let Some(ppd) = price_per_day {
-            // Validate price before updating
-            if ppd == 0 {
-                return err!(ErrorCode::InvalidPrice);
+            let mut item_account: Account<'info, ItemAccount> = Account::try_from(item_info)?;
+            if item_account.owner != owner {
+                msg!("Batch entry {} is not owned by the signer", index);
+                return err!(ErrorCode::Unauthorized);
             }
-            item_account.price_per_day = ppd;
-        }
-        if let Some(avail) = is_available { item_account.is_available = avail; }
-        if let Some(uri) = metadata_uri {
-            // Validate metadata_uri length before updating
-            if uri.len() > MAX_METADATA_URI_LENGTH {
-                return err!(ErrorCode::MetadataUriTooLong);
+            if item_account.registry != item_registry.key() {
+                msg!("Batch entry {} belongs to another registry", index);
+                return err!(ErrorCode::RegistryMismatch);
             }
-            item_account.metadata_uri = uri;
+
+            let was_available = item_account.is_available;
+            item_account.apply_update(args);
+            item_registry.check_item_prices(&item_account).map_err(|e| {
+                msg!("Batch entry {} is invalid", index);
+                e
+            })?;
+            item_registry.record_availability_change(was_available, item_account.is_available);
+            // Remaining accounts are not persisted automatically
+            item_account.exit(ctx.program_id)?;
         }
+
         Ok(())
     }
 
     pub fn remove_item(ctx: Context<RemoveItem>) -> Result<()> {
         let item_registry = &mut ctx.accounts.item_registry;
         let item_account = &ctx.accounts.item_account;
-        let item_id = item_account.item_id;
+        // Items cannot be closed while rental_flow still references them
+        require!(item_account.active_bookings == 0, ErrorCode::ItemHasActiveBookings);
         // Remove item_id from registry
-        if let Some(pos) = item_registry.item_ids.iter().position(|&id| id == item_id) {
-            item_registry.item_ids.swap_remove(pos);
-        } else {
-            // If item_id is not found in the list, return an error
-            return err!(ErrorCode::ItemNotFound);
+        item_registry.remove_item_id(item_account.item_id)?;
+        item_registry.record_listing_removed(item_account.is_available);
+        Ok(())
+    }
+
+    // Sets or clears (None) the listing expiry, and whether delist_expired_item may close the listing
+    // once it has expired. A listing already delisted by delist_expired_item stays unavailable until
+    // the owner re-enables it with set_item_availability.
+    pub fn renew_listing(ctx: Context<RenewListing>, expires_at: Option<i64>, close_on_expiry: bool) -> Result<()> {
+        if let Some(expiry) = expires_at {
+            require!(expiry > Clock::get()?.unix_timestamp, ErrorCode::InvalidListingExpiry);
         }
+        let item_account = &mut ctx.accounts.item_account;
+        item_account.expires_at = expires_at;
+        item_account.close_on_expiry = close_on_expiry;
         Ok(())
     }
+
+    // Permissionless crank: marks an expired listing unavailable and, if the owner opted in with
+    // close_on_expiry and the item has no unsettled bookings, removes it and refunds the rent to the
+    // owner. Items with bookings left are only delisted; the crank can close them once settled.
+    pub fn delist_expired_item(ctx: Context<DelistExpiredItem>) -> Result<()> {
+        let item_account = &mut ctx.accounts.item_account;
+        let item_registry = &mut ctx.accounts.item_registry;
+        let now = Clock::get()?.unix_timestamp;
+        require!(item_account.expires_at.map_or(false, |expiry| now >= expiry), ErrorCode::ListingNotExpired);
+
+        if item_account.is_available {
+            item_account.is_available = false;
+            item_registry.record_availability_change(true, false);
+        }
+
+        let close = item_account.close_on_expiry && item_account.active_bookings == 0;
+        if close {
+            item_registry.remove_item_id(item_account.item_id)?;
+            item_registry.record_listing_removed(false);
+            item_account.close(ctx.accounts.owner.to_account_info())?;
+        }
+        msg!("Item {} delisted (closed: {})", item_account.key(), close);
+        Ok(())
+    }
+
+    // Replaces the item's pricing rules (weekday, seasonal and surge adjustments)
+    pub fn set_pricing_rules(ctx: Context<SetPricingRules>, rules: Vec<PricingRule>) -> Result<()> {
+        if rules.len() > MAX_PRICING_RULES {
+            return err!(ErrorCode::TooManyPricingRules);
+        }
+        for (index, rule) in rules.iter().enumerate() {
+            rule.validate().map_err(|e| {
+                msg!("Pricing rule {} is invalid", index);
+                e
+            })?;
+        }
+        ctx.accounts.item_account.pricing_rules = rules;
+        Ok(())
+    }
+
+    // Switches the item to sub-hour booking slots (e.g. 15 minutes) at a flat rate per slot.
+    // `slot_minutes` = 0 switches back to whole hours at the hourly and daily rates.
+    pub fn set_slot_pricing(ctx: Context<SetSlotPricing>, slot_minutes: u16, price_per_slot: u64) -> Result<()> {
+        if slot_minutes != 0 {
+            require!(
+                slot_minutes < MINUTES_PER_HOUR && MINUTES_PER_HOUR % slot_minutes == 0 && price_per_slot > 0,
+                ErrorCode::InvalidSlotPricing
+            );
+        }
+        let item = &mut ctx.accounts.item_account;
+        item.slot_minutes = slot_minutes;
+        item.price_per_slot = if slot_minutes == 0 { 0 } else { price_per_slot };
+        ctx.accounts.item_registry.check_item_prices(item)
+    }
+
+    // Sets the hourly fee charged (from the security deposit) for returns more than `grace_minutes`
+    // after the rental ends. A zero fee disables late fees.
+    pub fn set_late_fee(ctx: Context<SetLateFee>, late_fee_per_hour: u64, grace_minutes: u16) -> Result<()> {
+        let item = &mut ctx.accounts.item_account;
+        item.late_fee_per_hour = late_fee_per_hour;
+        item.late_grace_minutes = grace_minutes;
+        Ok(())
+    }
+
     pub fn set_item_availability(ctx: Context<SetItemAvailabilityCpiAccounts>, available: bool) -> Result<()> {
+        let was_available = ctx.accounts.item_account.is_available;
         ctx.accounts.item_account.is_available = available;
+        ctx.accounts.item_registry.record_availability_change(was_available, available);
         msg!("Item {} availability set to: {}", ctx.accounts.item_account.key(), available);
         Ok(())
     }
 
+    // CPI-only: called by rental_flow to keep the marketplace rental counters up to date
+    pub fn record_rental_activity(ctx: Context<RecordRentalActivity>, activity: RentalActivity) -> Result<()> {
+        let item_account = &mut ctx.accounts.item_account;
+        let registry = &mut ctx.accounts.item_registry;
+        match activity {
+            RentalActivity::Confirmed => {
+                item_account.active_bookings = item_account.active_bookings.saturating_add(1);
+                registry.active_bookings = registry.active_bookings.saturating_add(1);
+            }
+            RentalActivity::Started => {
+                registry.currently_rented = registry.currently_rented.saturating_add(1);
+            }
+            RentalActivity::Completed { mint, amount, started } => {
+                item_account.active_bookings = item_account.active_bookings.saturating_sub(1);
+                registry.end_booking(started);
+                registry.lifetime_rentals = registry.lifetime_rentals.saturating_add(1);
+                registry.record_volume(mint, amount);
+            }
+            RentalActivity::Cancelled { started } => {
+                item_account.active_bookings = item_account.active_bookings.saturating_sub(1);
+                registry.end_booking(started);
+            }
+        }
+        Ok(())
+    }
+
+    // CPI-only: called by rental_flow for every new booking. Advances the item's rental nonce,
+    // which seeds the booking's RentalTransaction so a renter can rent the same item again.
+    pub fn claim_rental_nonce(ctx: Context<ClaimRentalNonce>) -> Result<()> {
+        let item_account = &mut ctx.accounts.item_account;
+        item_account.next_rental_nonce = item_account.next_rental_nonce.checked_add(1)
+            .ok_or_else(|| error!(ErrorCode::RentalNonceOverflow))?;
+        Ok(())
+    }
+
+    // CPI-only: called by rental_flow once per completed rental with the renter's score
+    pub fn record_rating(ctx: Context<RecordRating>, score: u8) -> Result<()> {
+        require!((MIN_RATING..=MAX_RATING).contains(&score), ErrorCode::InvalidRating);
+        let item_account = &mut ctx.accounts.item_account;
+        item_account.rating_sum = item_account.rating_sum.checked_add(score as u64)
+            .ok_or_else(|| error!(ErrorCode::RatingOverflow))?;
+        item_account.rating_count = item_account.rating_count.checked_add(1)
+            .ok_or_else(|| error!(ErrorCode::RatingOverflow))?;
+        Ok(())
+    }
+
+    pub fn create_user_profile(
+        ctx: Context<CreateUserProfile>,
+        display_name: String,
+        avatar_uri: String,
+        contact_preferences: u8,
+    ) -> Result<()> {
+        validate_profile_fields(Some(&display_name), Some(&avatar_uri), Some(contact_preferences))?;
+
+        let profile = &mut ctx.accounts.user_profile;
+        profile.wallet = ctx.accounts.wallet.key();
+        profile.display_name = display_name;
+        profile.avatar_uri = avatar_uri;
+        profile.contact_preferences = contact_preferences;
+        profile.joined_at = Clock::get()?.unix_timestamp;
+        profile.rentals_as_owner = 0;
+        profile.rentals_as_renter = 0;
+        profile.bump = ctx.bumps.user_profile;
+        Ok(())
+    }
+
+    pub fn update_user_profile(
+        ctx: Context<UpdateUserProfile>,
+        display_name: Option<String>,
+        avatar_uri: Option<String>,
+        contact_preferences: Option<u8>,
+    ) -> Result<()> {
+        validate_profile_fields(display_name.as_ref(), avatar_uri.as_ref(), contact_preferences)?;
+
+        let profile = &mut ctx.accounts.user_profile;
+        if let Some(name) = display_name { profile.display_name = name; }
+        if let Some(uri) = avatar_uri { profile.avatar_uri = uri; }
+        if let Some(prefs) = contact_preferences { profile.contact_preferences = prefs; }
+        Ok(())
+    }
+
+    // Issuer-only: attests that `subject` has completed identity verification
+    pub fn issue_attestation(ctx: Context<IssueAttestation>, expires_at: Option<i64>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        if let Some(expiry) = expires_at {
+            require!(expiry > now, ErrorCode::InvalidAttestationExpiry);
+        }
+
+        let attestation = &mut ctx.accounts.attestation;
+        attestation.issuer = ctx.accounts.issuer.key();
+        attestation.subject = ctx.accounts.subject.key();
+        attestation.issued_at = now;
+        attestation.expires_at = expires_at;
+        attestation.bump = ctx.bumps.attestation;
+        Ok(())
+    }
+
+    // Issuer-only: revokes an attestation by closing it
+    pub fn revoke_attestation(_ctx: Context<RevokeAttestation>) -> Result<()> {
+        Ok(())
+    }
+
+    // CPI-only: called by rental_flow when a rental completes, once per participant profile
+    pub fn record_profile_rental(ctx: Context<RecordProfileRental>, role: ProfileRole) -> Result<()> {
+        let profile = &mut ctx.accounts.user_profile;
+        match role {
+            ProfileRole::Owner => profile.rentals_as_owner = profile.rentals_as_owner.saturating_add(1),
+            ProfileRole::Renter => profile.rentals_as_renter = profile.rentals_as_renter.saturating_add(1),
+        }
+        Ok(())
+    }
+
 }
 
 #[derive(Accounts)]
+#[instruction(namespace: String)]
 pub struct InitializeRegistry<'info> {
-    #[account(init, payer = payer, space = 8 + 8 + 4 + 1000 * 8)]
+    #[account(init, payer = payer, space = ItemRegistry::LEN, seeds = [REGISTRY_SEED, namespace.as_bytes()], bump)]
     pub item_registry: Account<'info, ItemRegistry>,
     #[account(mut)]
-    pub payer: Signer<'info>,
+    pub payer: Signer<'info>, // Upgrade authority of this program
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()) @ ErrorCode::Unauthorized)]
+    pub program: Program<'info, crate::program::ItemRegistration>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(payer.key()) @ ErrorCode::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
     pub system_program: Program<'info, System>,
 }
 
@@ -156,30 +553,412 @@ pub struct InitializeRegistry<'info> {
 pub struct ItemAccount {
     pub owner: Pubkey,
     pub item_id: u64,
+    pub registry: Pubkey, // Registry (marketplace) the item is listed in
     pub name: String,        // Max 60 characters (enforced by MAX_NAME_LENGTH)
     pub description: String, // Max 250 characters (enforced by MAX_DESCRIPTION_LENGTH)
     pub price_per_hour: u64,
     pub price_per_day: u64,
-    pub is_available: bool,
+    pub is_available: bool, // Open for bookings; rental windows are tracked by rental_flow's BookingSchedule
     pub metadata_uri: String,
+    pub rating_sum: u64,   // Sum of renter scores (1-5) from completed rentals
+    pub rating_count: u64, // Number of scores; average = rating_sum / rating_count
+    pub category: u8,      // One of the CATEGORY_* values
+    pub requires_verified_renter: bool, // Renters must hold a valid attestation
+    pub pricing_rules: Vec<PricingRule>, // Max MAX_PRICING_RULES, applied per rented hour
+    pub expires_at: Option<i64>, // Listing expiry, renewed by the owner; None never expires
+    pub active_bookings: u32,    // Confirmed bookings not yet settled (upcoming or running), maintained via CPI from rental_flow
+    pub security_deposit: u64,   // Escrowed with the rental price and returned to the renter afterwards
+    pub slot_minutes: u16,       // Sub-hour booking slot length; 0 books whole hours
+    pub price_per_slot: u64,     // Flat rate per slot, used instead of the hourly/daily rates when slot_minutes is set
+    pub price_mint: Pubkey,      // Mint the prices are denominated in; rentals are paid in this mint
+    pub price_decimals: u8,      // Decimals of price_mint, prices are in its base units
+    pub booking_mode: BookingMode,
+    pub next_rental_nonce: u64,  // Seeds the next booking's RentalTransaction in rental_flow
+    pub cancellation_policy: CancellationPolicy, // Copied onto each booking, so later changes only affect new rentals
+    pub late_fee_per_hour: u64,  // Charged from the deposit per started hour of a late return; copied onto each booking
+    pub late_grace_minutes: u16, // Returns up to this late are not charged
+    pub close_on_expiry: bool,   // Set by the owner; delist_expired_item closes the expired listing
+}
+
+// How renters book an item in rental_flow
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum BookingMode {
+    Instant,       // The renter books alone and the rental starts right away
+    RequestToBook, // The renter escrows funds in a request the owner accepts or rejects
+}
+
+// Refund a renter gets when cancelling a booking alone in rental_flow. The security deposit is
+// always returned in full.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum CancellationPolicy {
+    Flexible, // Full refund until the rental starts
+    Moderate, // Full refund until 48 hours before the start, 50% until the start
+    Strict,   // 50% refund until 7 days before the start
+}
+
+impl CancellationPolicy {
+    // Share of the rental price refunded for a cancellation at `now`, in basis points.
+    // Nothing is refunded once the rental has started.
+    pub fn refund_bps(&self, start_time: i64, now: i64) -> u16 {
+        let notice = start_time.saturating_sub(now);
+        if notice <= 0 {
+            return 0;
+        }
+        match self {
+            CancellationPolicy::Flexible => FULL_REFUND_BPS,
+            CancellationPolicy::Moderate if notice >= MODERATE_FULL_REFUND_NOTICE_SECONDS => FULL_REFUND_BPS,
+            CancellationPolicy::Moderate => PARTIAL_REFUND_BPS,
+            CancellationPolicy::Strict if notice >= STRICT_PARTIAL_REFUND_NOTICE_SECONDS => PARTIAL_REFUND_BPS,
+            CancellationPolicy::Strict => 0,
+        }
+    }
+}
+
+// A price adjustment applied to every rented hour that matches `condition`.
+// Adjustments of all matching rules are added together.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct PricingRule {
+    pub condition: RuleCondition,
+    pub adjustment: RuleAdjustment,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RuleCondition {
+    DaysOfWeek { mask: u8 },           // Bit 0 = Sunday ... bit 6 = Saturday (UTC)
+    DateRange { start: i64, end: i64 }, // Unix timestamps, [start, end)
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RuleAdjustment {
+    Percentage { bps: i16 },  // Basis points of the hour's base price, e.g. 2000 = +20%, -1500 = -15%
+    Absolute { amount: i64 }, // Base units added to (or subtracted from) the hour's price
+}
+
+impl PricingRule {
+    // 1 + 16 (largest RuleCondition variant) + 1 + 8 (largest RuleAdjustment variant)
+    pub const LEN: usize = 1 + 16 + 1 + 8;
+
+    fn validate(&self) -> Result<()> {
+        match self.condition {
+            RuleCondition::DaysOfWeek { mask } => {
+                require!(mask != 0 && mask < (1 << 7), ErrorCode::InvalidPricingRule);
+            }
+            RuleCondition::DateRange { start, end } => {
+                require!(start < end, ErrorCode::InvalidPricingRule);
+            }
+        }
+        match self.adjustment {
+            RuleAdjustment::Percentage { bps } => {
+                require!(bps != 0 && (-MAX_RULE_ADJUSTMENT_BPS..=MAX_RULE_ADJUSTMENT_BPS).contains(&bps), ErrorCode::InvalidPricingRule);
+            }
+            RuleAdjustment::Absolute { amount } => {
+                require!(amount != 0, ErrorCode::InvalidPricingRule);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl HourlyRule for PricingRule {
+    fn applies_to(&self, unit_start: i64) -> bool {
+        self.condition.matches(unit_start)
+    }
+
+    fn adjustment(&self) -> Adjustment {
+        match self.adjustment {
+            RuleAdjustment::Percentage { bps } => Adjustment::Percentage { bps },
+            RuleAdjustment::Absolute { amount } => Adjustment::Absolute { amount },
+        }
+    }
+}
+
+impl RuleCondition {
+    // True if the hour starting at `timestamp` is covered by this condition
+    pub fn matches(&self, timestamp: i64) -> bool {
+        match *self {
+            RuleCondition::DaysOfWeek { mask } => {
+                // 1970-01-01 was a Thursday (day 4 when Sunday is day 0)
+                let day_of_week = (timestamp.div_euclid(86_400) + 4).rem_euclid(7);
+                mask & (1 << day_of_week) != 0
+            }
+            RuleCondition::DateRange { start, end } => timestamp >= start && timestamp < end,
+        }
+    }
+}
+
+#[derive(Accounts)]
+pub struct SetPriceBounds<'info> {
+    pub authority: Signer<'info>,
+    #[account(mut, has_one = authority @ ErrorCode::Unauthorized)]
+    pub item_registry: Account<'info, ItemRegistry>,
+}
+
+#[derive(Accounts)]
+pub struct CloseRegistry<'info> {
+    pub authority: Signer<'info>,
+    #[account(mut, has_one = authority @ ErrorCode::Unauthorized, close = recipient)]
+    pub item_registry: Account<'info, ItemRegistry>,
+    /// CHECK: Any account chosen by the authority to receive the registry's rent
+    #[account(mut)]
+    pub recipient: UncheckedAccount<'info>,
 }
 
 #[account]
 pub struct ItemRegistry {
+    pub namespace: String, // Marketplace name, max 32 characters; part of the PDA seeds
+    pub bump: u8,
+    pub authority: Pubkey, // May close the registry once it is empty
     pub next_item_id: u64, // Stores the ID to be assigned to the next registered item
     // Stores item IDs. Capacity is limited by account size (currently MAX_REGISTRY_ITEMS, ~1000 items).
     pub item_ids: Vec<u64>,
+    // Marketplace statistics, maintained by this program and via CPI from rental_flow
+    pub total_listed: u64,        // Items currently registered
+    pub currently_available: u64, // Registered items flagged as available
+    pub active_bookings: u64,     // Confirmed bookings not yet settled, including future reservations
+    pub currently_rented: u64,    // Active bookings whose rental period has begun
+    pub lifetime_rentals: u64,    // Rentals completed since the registry was created
+    pub mint_volumes: Vec<MintVolume>, // Completed rental volume per payment mint (max MAX_TRACKED_MINTS)
+    // Accepted range for each kind of item rate
+    pub hourly_price_bounds: PriceBounds,
+    pub daily_price_bounds: PriceBounds,
+    pub slot_price_bounds: PriceBounds,
+}
+
+// Accepted range for one kind of item rate, in hundredths of a whole token of the item's price mint
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct PriceBounds {
+    pub min: u64,
+    pub max: u64,
+}
+
+impl PriceBounds {
+    pub const LEN: usize = 2 * 8;
+    const UNBOUNDED: Self = Self { min: 0, max: u64::MAX };
+
+    // Checks a price in base units of a mint with `decimals` against the range
+    // Scaled bounds beyond u128 (e.g. the default max with 20+ decimals) saturate instead of overflowing
+    fn check(&self, price: u64, decimals: u8) -> Result<()> {
+        let one_token = 10u128.saturating_pow(decimals as u32);
+        let price = price as u128 * 10u128.pow(PRICE_BOUND_DECIMALS);
+        let min_price = (self.min as u128).saturating_mul(one_token);
+        let max_price = (self.max as u128).saturating_mul(one_token);
+        require!(price >= min_price && price <= max_price, ErrorCode::PriceOutOfBounds);
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct MintVolume {
+    pub mint: Pubkey,
+    pub volume: u64,
+}
+
+// Rental lifecycle events reported by rental_flow
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RentalActivity {
+    Confirmed, // Instant booking made, or request accepted; the rental may start later
+    Started,   // The rental period of a confirmed booking has begun
+    // A confirmed booking was settled; `started` if Started was reported for it
+    Completed { mint: Pubkey, amount: u64, started: bool },
+    Cancelled { started: bool },
+}
+
+impl ItemRegistry {
+    // 8 (discriminator) + 4 + 32 (namespace) + 1 (bump) + 32 (authority) + 8 (next_item_id)
+    // + 4 + 1000*8 (item_ids) + 5*8 (counters) + 4 + MAX_TRACKED_MINTS*(32 + 8) (mint_volumes)
+    // + 3*16 (hourly, daily and slot price bounds)
+    pub const LEN: usize = 8 + 4 + MAX_NAMESPACE_LENGTH + 1 + 32 + 8 + 4 + MAX_REGISTRY_ITEMS * 8 + 5 * 8
+        + 4 + MAX_TRACKED_MINTS * (32 + 8) + 3 * PriceBounds::LEN;
+
+    fn remove_item_id(&mut self, item_id: u64) -> Result<()> {
+        if let Some(pos) = self.item_ids.iter().position(|&id| id == item_id) {
+            self.item_ids.swap_remove(pos);
+            Ok(())
+        } else {
+            // If item_id is not found in the list, return an error
+            err!(ErrorCode::ItemNotFound)
+        }
+    }
+
+    // Increments next_item_id and records the newly assigned item_id
+    fn assign_item_id(&mut self) -> u64 {
+        self.next_item_id += 1;
+        let item_id = self.next_item_id;
+        self.item_ids.push(item_id);
+        item_id
+    }
+
+    // New listings start out available
+    fn record_listing_added(&mut self) {
+        self.total_listed = self.total_listed.saturating_add(1);
+        self.currently_available = self.currently_available.saturating_add(1);
+    }
+
+    fn record_listing_removed(&mut self, was_available: bool) {
+        self.total_listed = self.total_listed.saturating_sub(1);
+        if was_available {
+            self.currently_available = self.currently_available.saturating_sub(1);
+        }
+    }
+
+    // A confirmed booking was settled, and had been running if `started`
+    fn end_booking(&mut self, started: bool) {
+        self.active_bookings = self.active_bookings.saturating_sub(1);
+        if started {
+            self.currently_rented = self.currently_rented.saturating_sub(1);
+        }
+    }
+
+    fn record_availability_change(&mut self, was_available: bool, is_available: bool) {
+        if !was_available && is_available {
+            self.currently_available = self.currently_available.saturating_add(1);
+        } else if was_available && !is_available {
+            self.currently_available = self.currently_available.saturating_sub(1);
+        }
+    }
+
+    fn check_item_prices(&self, item: &ItemAccount) -> Result<()> {
+        self.hourly_price_bounds.check(item.price_per_hour, item.price_decimals)?;
+        self.daily_price_bounds.check(item.price_per_day, item.price_decimals)?;
+        if item.slot_minutes != 0 {
+            self.slot_price_bounds.check(item.price_per_slot, item.price_decimals)?;
+        }
+        Ok(())
+    }
+
+    fn record_volume(&mut self, mint: Pubkey, amount: u64) {
+        if let Some(entry) = self.mint_volumes.iter_mut().find(|entry| entry.mint == mint) {
+            entry.volume = entry.volume.saturating_add(amount);
+        } else if self.mint_volumes.len() < MAX_TRACKED_MINTS {
+            self.mint_volumes.push(MintVolume { mint, volume: amount });
+        } else {
+            // Statistics must never block a rental, so untracked mints are only logged
+            msg!("Volume for mint {} not tracked: mint list is full", mint);
+        }
+    }
+}
+
+// Item details supplied when registering an item (single or batch)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ItemRegistrationArgs {
+    pub name: String,
+    pub description: String,
+    pub price_per_hour: u64,
+    pub price_per_day: u64,
+    pub metadata_uri: String,
+    pub category: u8,
+    pub requires_verified_renter: bool,
+    pub expires_at: Option<i64>,
+    pub security_deposit: u64,
+    pub booking_mode: BookingMode,
+    pub cancellation_policy: CancellationPolicy,
+}
+
+impl ItemRegistrationArgs {
+    fn validate(&self) -> Result<()> {
+        // Check name length
+        if self.name.len() > MAX_NAME_LENGTH {
+            return err!(ErrorCode::NameTooLong);
+        }
+        // Check description length
+        if self.description.len() > MAX_DESCRIPTION_LENGTH {
+            return err!(ErrorCode::DescriptionTooLong);
+        }
+        // Check metadata_uri length
+        if self.metadata_uri.len() > MAX_METADATA_URI_LENGTH {
+            return err!(ErrorCode::MetadataUriTooLong);
+        }
+        // Check price validity
+        if self.price_per_hour == 0 || self.price_per_day == 0 {
+            return err!(ErrorCode::InvalidPrice);
+        }
+        // Check category validity
+        if self.category >= CATEGORY_COUNT {
+            return err!(ErrorCode::InvalidCategory);
+        }
+        // Check listing expiry is in the future
+        if let Some(expiry) = self.expires_at {
+            if expiry <= Clock::get()?.unix_timestamp {
+                return err!(ErrorCode::InvalidListingExpiry);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Fields to change on an existing item (single or batch); None leaves a field untouched
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ItemUpdateArgs {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub price_per_hour: Option<u64>,
+    pub price_per_day: Option<u64>,
+    pub is_available: Option<bool>,
+    pub metadata_uri: Option<String>,
+    pub requires_verified_renter: Option<bool>,
+    pub security_deposit: Option<u64>,
+    pub booking_mode: Option<BookingMode>,
+    pub cancellation_policy: Option<CancellationPolicy>,
+}
+
+impl ItemUpdateArgs {
+    fn validate(&self) -> Result<()> {
+        if let Some(n) = &self.name {
+            if n.len() > MAX_NAME_LENGTH {
+                return err!(ErrorCode::NameTooLong);
+            }
+        }
+        if let Some(d) = &self.description {
+            if d.len() > MAX_DESCRIPTION_LENGTH {
+                return err!(ErrorCode::DescriptionTooLong);
+            }
+        }
+        if self.price_per_hour == Some(0) || self.price_per_day == Some(0) {
+            return err!(ErrorCode::InvalidPrice);
+        }
+        if let Some(uri) = &self.metadata_uri {
+            if uri.len() > MAX_METADATA_URI_LENGTH {
+                return err!(ErrorCode::MetadataUriTooLong);
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Accounts)]
 pub struct RegisterItem<'info> {
     #[account(mut)]
     pub owner: Signer<'info>,
-    #[account(init, payer = owner, space = 8 + 32 + 8 + 4 + 64 + 4 + 256 + 8 + 8 + 1 + 4 + 256)] // adjust space as needed
+    #[account(mut)]
+    pub item_registry: Account<'info, ItemRegistry>,
+    // Created at the PDA of the ID the item is about to be assigned
+    #[account(
+        init,
+        payer = owner,
+        space = ItemAccount::LEN,
+        seeds = [ITEM_SEED, item_registry.key().as_ref(), (item_registry.next_item_id + 1).to_le_bytes().as_ref()],
+        bump
+    )]
     pub item_account: Account<'info, ItemAccount>,
+    pub price_mint: Account<'info, Mint>, // Mint the item's prices are denominated in
+    // Owner's identity attestation, required for gated categories
+    #[account(seeds = [ATTESTATION_SEED, owner.key().as_ref()], bump)]
+    pub attestation: Option<Account<'info, Attestation>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterItems<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
     #[account(mut)]
     pub item_registry: Account<'info, ItemRegistry>,
+    pub price_mint: Account<'info, Mint>, // Mint the prices of every entry are denominated in
+    // Owner's identity attestation, required if any entry is in a gated category
+    #[account(seeds = [ATTESTATION_SEED, owner.key().as_ref()], bump)]
+    pub attestation: Option<Account<'info, Attestation>>,
     pub system_program: Program<'info, System>,
+    // remaining_accounts: one new item account (writable item PDA) per batch entry
 }
 
 #[derive(Accounts)]
@@ -188,6 +967,59 @@ pub struct UpdateItem<'info> {
     pub owner: Signer<'info>,
     #[account(mut, has_one = owner)]
     pub item_account: Account<'info, ItemAccount>,
+    #[account(mut, constraint = item_registry.key() == item_account.registry @ ErrorCode::RegistryMismatch)]
+    pub item_registry: Account<'info, ItemRegistry>,
+    pub price_mint: Option<Account<'info, Mint>>, // New price mint, if the item is re-priced in another mint
+}
+
+#[derive(Accounts)]
+pub struct RenewListing<'info> {
+    pub owner: Signer<'info>,
+    #[account(mut, has_one = owner @ ErrorCode::Unauthorized)]
+    pub item_account: Account<'info, ItemAccount>,
+}
+
+#[derive(Accounts)]
+pub struct DelistExpiredItem<'info> {
+    #[account(mut)]
+    pub item_account: Account<'info, ItemAccount>,
+    #[account(mut, constraint = item_registry.key() == item_account.registry @ ErrorCode::RegistryMismatch)]
+    pub item_registry: Account<'info, ItemRegistry>,
+    /// CHECK: Receives the rent if the item is closed; must be the item's owner
+    #[account(mut, address = item_account.owner)]
+    pub owner: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPricingRules<'info> {
+    pub owner: Signer<'info>,
+    #[account(mut, has_one = owner @ ErrorCode::Unauthorized)]
+    pub item_account: Account<'info, ItemAccount>,
+}
+
+#[derive(Accounts)]
+pub struct SetLateFee<'info> {
+    pub owner: Signer<'info>,
+    #[account(mut, has_one = owner @ ErrorCode::Unauthorized)]
+    pub item_account: Account<'info, ItemAccount>,
+}
+
+#[derive(Accounts)]
+pub struct SetSlotPricing<'info> {
+    pub owner: Signer<'info>,
+    #[account(mut, has_one = owner @ ErrorCode::Unauthorized)]
+    pub item_account: Account<'info, ItemAccount>,
+    #[account(constraint = item_registry.key() == item_account.registry @ ErrorCode::RegistryMismatch)]
+    pub item_registry: Account<'info, ItemRegistry>, // Provides the accepted price range
+}
+
+#[derive(Accounts)]
+pub struct UpdateItems<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(mut)]
+    pub item_registry: Account<'info, ItemRegistry>,
+    // remaining_accounts: one writable item account owned by `owner` per batch entry
 }
 
 #[derive(Accounts)]
@@ -196,40 +1028,281 @@ pub struct RemoveItem<'info> {
     pub owner: Signer<'info>,
     #[account(mut, has_one = owner, close = owner)]
     pub item_account: Account<'info, ItemAccount>,
-    #[account(mut)]
+    #[account(mut, constraint = item_registry.key() == item_account.registry @ ErrorCode::RegistryMismatch)]
     pub item_registry: Account<'info, ItemRegistry>,
 }
 
 #[derive(Accounts)]
 pub struct SetItemAvailabilityCpiAccounts<'info> {
-    #[account(mut, has_one = owner @ ErrorCode::Unauthorized)] // Ensure owner is the authority
+    #[account(mut)]
+    pub item_account: Account<'info, ItemAccount>,
+    // The owner of the item
+    #[account(constraint = authority.key() == item_account.owner @ ErrorCode::Unauthorized)]
+    pub authority: Signer<'info>,
+    #[account(mut, constraint = item_registry.key() == item_account.registry @ ErrorCode::RegistryMismatch)]
+    pub item_registry: Account<'info, ItemRegistry>,
+}
+
+#[derive(Accounts)]
+pub struct RecordRentalActivity<'info> {
+    #[account(mut)]
+    pub item_account: Account<'info, ItemAccount>,
+    #[account(mut, constraint = item_registry.key() == item_account.registry @ ErrorCode::RegistryMismatch)]
+    pub item_registry: Account<'info, ItemRegistry>,
+    #[account(constraint = is_rental_authority(&rental_authority.key()) @ ErrorCode::Unauthorized)]
+    pub rental_authority: Signer<'info>, // rental_flow's PDA signer
+}
+
+#[derive(Accounts)]
+pub struct ClaimRentalNonce<'info> {
+    #[account(mut)]
+    pub item_account: Account<'info, ItemAccount>,
+    #[account(constraint = is_rental_authority(&rental_authority.key()) @ ErrorCode::Unauthorized)]
+    pub rental_authority: Signer<'info>, // rental_flow's PDA signer
+}
+
+#[derive(Accounts)]
+pub struct RecordRating<'info> {
+    #[account(mut)]
     pub item_account: Account<'info, ItemAccount>,
-    pub owner: Signer<'info>, // The owner of the item, must sign
+    #[account(constraint = is_rental_authority(&rental_authority.key()) @ ErrorCode::Unauthorized)]
+    pub rental_authority: Signer<'info>, // rental_flow's PDA signer
+}
+
+#[account]
+pub struct UserProfile {
+    pub wallet: Pubkey,
+    pub display_name: String, // Max 32 characters (enforced by MAX_DISPLAY_NAME_LENGTH)
+    pub avatar_uri: String,   // Max 200 characters (enforced by MAX_AVATAR_URI_LENGTH)
+    pub contact_preferences: u8, // Bitmask of CONTACT_* flags
+    pub joined_at: i64,
+    pub rentals_as_owner: u64,  // Completed rentals of this wallet's items
+    pub rentals_as_renter: u64, // Completed rentals by this wallet
+    pub bump: u8,
+}
+
+impl UserProfile {
+    // 8 (discriminator) + 32 (wallet) + 4 + 32 (display_name) + 4 + 200 (avatar_uri)
+    // + 1 (contact_preferences) + 8 (joined_at) + 2*8 (counters) + 1 (bump)
+    pub const LEN: usize = 8 + 32 + 4 + MAX_DISPLAY_NAME_LENGTH + 4 + MAX_AVATAR_URI_LENGTH + 1 + 8 + 8 + 8 + 1;
+}
+
+#[account]
+pub struct Attestation {
+    pub issuer: Pubkey,
+    pub subject: Pubkey, // Wallet whose identity has been verified
+    pub issued_at: i64,
+    pub expires_at: Option<i64>, // None for attestations that do not expire
+    pub bump: u8,
+}
+
+impl Attestation {
+    // 8 (discriminator) + 2*32 (issuer, subject) + 8 (issued_at) + 1 + 8 (expires_at) + 1 (bump)
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 1 + 8 + 1;
+
+    // True if this attestation was issued by the trusted issuer for `subject` and has not expired
+    pub fn is_valid_for(&self, subject: &Pubkey, now: i64) -> bool {
+        is_attestation_issuer(&self.issuer)
+            && self.subject == *subject
+            && self.expires_at.map_or(true, |expiry| now < expiry)
+    }
+}
+
+#[derive(Accounts)]
+pub struct IssueAttestation<'info> {
+    #[account(mut, constraint = is_attestation_issuer(&issuer.key()) @ ErrorCode::Unauthorized)]
+    pub issuer: Signer<'info>,
+    /// CHECK: Wallet being attested; only its key is used
+    pub subject: UncheckedAccount<'info>,
+    #[account(init, payer = issuer, space = Attestation::LEN, seeds = [ATTESTATION_SEED, subject.key().as_ref()], bump)]
+    pub attestation: Account<'info, Attestation>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeAttestation<'info> {
+    #[account(mut)]
+    pub issuer: Signer<'info>,
+    #[account(mut, has_one = issuer @ ErrorCode::Unauthorized, close = issuer)]
+    pub attestation: Account<'info, Attestation>,
+}
+
+// Which side of a rental a profile counter update is for
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileRole {
+    Owner,
+    Renter,
+}
+
+#[derive(Accounts)]
+pub struct CreateUserProfile<'info> {
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+    #[account(init, payer = wallet, space = UserProfile::LEN, seeds = [USER_PROFILE_SEED, wallet.key().as_ref()], bump)]
+    pub user_profile: Account<'info, UserProfile>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateUserProfile<'info> {
+    pub wallet: Signer<'info>,
+    #[account(mut, seeds = [USER_PROFILE_SEED, wallet.key().as_ref()], bump = user_profile.bump, has_one = wallet @ ErrorCode::Unauthorized)]
+    pub user_profile: Account<'info, UserProfile>,
+}
+
+#[derive(Accounts)]
+pub struct RecordProfileRental<'info> {
+    #[account(mut, seeds = [USER_PROFILE_SEED, user_profile.wallet.as_ref()], bump = user_profile.bump)]
+    pub user_profile: Account<'info, UserProfile>,
+    #[account(constraint = is_rental_authority(&rental_authority.key()) @ ErrorCode::Unauthorized)]
+    pub rental_authority: Signer<'info>, // rental_flow's PDA signer
+}
+
+// Validates the profile fields that are being set
+fn validate_profile_fields(display_name: Option<&String>, avatar_uri: Option<&String>, contact_preferences: Option<u8>) -> Result<()> {
+    if let Some(name) = display_name {
+        if name.len() > MAX_DISPLAY_NAME_LENGTH {
+            return err!(ErrorCode::DisplayNameTooLong);
+        }
+    }
+    if let Some(uri) = avatar_uri {
+        if uri.len() > MAX_AVATAR_URI_LENGTH {
+            return err!(ErrorCode::AvatarUriTooLong);
+        }
+    }
+    if let Some(prefs) = contact_preferences {
+        if prefs & !CONTACT_PREFERENCE_MASK != 0 {
+            return err!(ErrorCode::InvalidContactPreferences);
+        }
+    }
+    Ok(())
+}
+
+fn is_attestation_issuer(key: &Pubkey) -> bool {
+    Pubkey::from_str(ATTESTATION_ISSUER).map_or(false, |issuer| issuer == *key)
+}
+
+// Gated categories require the owner to hold a valid attestation from the trusted issuer
+fn require_listing_attestation(category: u8, owner: &Pubkey, attestation: Option<&Attestation>) -> Result<()> {
+    if GATED_CATEGORIES.contains(&category) {
+        let now = Clock::get()?.unix_timestamp;
+        let verified = attestation.map_or(false, |a| a.is_valid_for(owner, now));
+        require!(verified, ErrorCode::AttestationRequired);
+    }
+    Ok(())
+}
+
+// Checks that `key` is the PDA rental_flow signs its CPIs with
+fn is_rental_authority(key: &Pubkey) -> bool {
+    match Pubkey::from_str(RENTAL_FLOW_PROGRAM_ID) {
+        Ok(rental_flow_id) => Pubkey::find_program_address(&[RENTAL_AUTHORITY_SEED], &rental_flow_id).0 == *key,
+        Err(_) => false,
+    }
+}
+
+// Creates a rent-exempt account of `space` bytes owned by this program at the PDA signed for by
+// `seeds`. Item PDAs are predictable, so like Anchor's init, a PDA that was already sent lamports
+// is topped up, allocated and assigned instead, since create_account fails on an account with a
+// balance.
+fn create_program_account<'info>(
+    payer: &AccountInfo<'info>,
+    info: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space);
+    let program = system_program.to_account_info();
+    if info.lamports() == 0 {
+        return system_program::create_account(
+            CpiContext::new_with_signer(program, CreateAccount { from: payer.clone(), to: info.clone() }, &[seeds]),
+            rent,
+            space as u64,
+            &crate::ID,
+        );
+    }
+    let top_up = rent.saturating_sub(info.lamports());
+    if top_up > 0 {
+        system_program::transfer(
+            CpiContext::new(program.clone(), system_program::Transfer { from: payer.clone(), to: info.clone() }),
+            top_up,
+        )?;
+    }
+    system_program::allocate(
+        CpiContext::new_with_signer(program.clone(), system_program::Allocate { account_to_allocate: info.clone() }, &[seeds]),
+        space as u64,
+    )?;
+    system_program::assign(
+        CpiContext::new_with_signer(program, system_program::Assign { account_to_assign: info.clone() }, &[seeds]),
+        &crate::ID,
+    )
 }
 
 impl ItemAccount {
-    pub fn calculate_total_price(&self, hours: u64) -> Result<u64> {
-        // Calculate price based on hours only
-        let total_hour_price = self.price_per_hour.checked_mul(hours)
-            .ok_or_else(|| error!(ErrorCode::PriceCalculationOverflow))?;
-
-        // Calculate price based on days and remaining hours
-        let days = hours / 24;
-        let remaining_hours = hours % 24;
-
-        let day_rate_total = self.price_per_day.checked_mul(days)
-            .ok_or_else(|| error!(ErrorCode::PriceCalculationOverflow))?;
-        let remaining_hour_rate_total = self.price_per_hour.checked_mul(remaining_hours)
-            .ok_or_else(|| error!(ErrorCode::PriceCalculationOverflow))?;
-        
-        let total_day_price = day_rate_total.checked_add(remaining_hour_rate_total)
-            .ok_or_else(|| error!(ErrorCode::PriceCalculationOverflow))?;
-
-        // Return the minimum of the two calculation methods
-        if total_hour_price > total_day_price {
-            Ok(total_day_price)
-        } else {
-            Ok(total_hour_price)
+    // 8 (discriminator) + 32 (owner) + 8 (item_id) + 32 (registry) + 4 + 64 (name) + 4 + 256 (description)
+    // + 2*8 (prices) + 1 (is_available) + 4 + 256 (metadata_uri) + 2*8 (rating_sum, rating_count)
+    // + 1 (category) + 1 (requires_verified_renter) + 4 + MAX_PRICING_RULES*PricingRule::LEN (pricing_rules)
+    // + 1 + 8 (expires_at) + 4 (active_bookings) + 8 (security_deposit) + 2 (slot_minutes) + 8 (price_per_slot)
+    // + 32 (price_mint) + 1 (price_decimals) + 1 (booking_mode) + 8 (next_rental_nonce) + 1 (cancellation_policy)
+    // + 8 (late_fee_per_hour) + 2 (late_grace_minutes) + 1 (close_on_expiry)
+    pub const LEN: usize = 8 + 32 + 8 + 32 + 4 + 64 + 4 + 256 + 8 + 8 + 1 + 4 + 256 + 8 + 8 + 1 + 1
+        + 4 + MAX_PRICING_RULES * PricingRule::LEN + 1 + 8 + 4 + 8 + 2 + 8 + 32 + 1 + 1 + 8 + 1 + 8 + 2 + 1;
+
+    fn new(owner: Pubkey, item_id: u64, registry: Pubkey, price_mint: Pubkey, price_decimals: u8, args: ItemRegistrationArgs) -> Self {
+        ItemAccount {
+            owner,
+            item_id,
+            registry,
+            name: args.name,
+            description: args.description,
+            price_per_hour: args.price_per_hour,
+            price_per_day: args.price_per_day,
+            is_available: true,
+            metadata_uri: args.metadata_uri,
+            rating_sum: 0,
+            rating_count: 0,
+            category: args.category,
+            requires_verified_renter: args.requires_verified_renter,
+            pricing_rules: Vec::new(),
+            expires_at: args.expires_at,
+            active_bookings: 0,
+            security_deposit: args.security_deposit,
+            slot_minutes: 0,
+            price_per_slot: 0,
+            price_mint,
+            price_decimals,
+            booking_mode: args.booking_mode,
+            next_rental_nonce: 0,
+            cancellation_policy: args.cancellation_policy,
+            late_fee_per_hour: 0,
+            late_grace_minutes: 0,
+            close_on_expiry: false,
+        }
+    }
+
+    // Applies an already validated update
+    fn apply_update(&mut self, args: ItemUpdateArgs) {
+        if let Some(n) = args.name { self.name = n; }
+        if let Some(d) = args.description { self.description = d; }
+        if let Some(pph) = args.price_per_hour { self.price_per_hour = pph; }
+        if let Some(ppd) = args.price_per_day { self.price_per_day = ppd; }
+        if let Some(avail) = args.is_available { self.is_available = avail; }
+        if let Some(uri) = args.metadata_uri { self.metadata_uri = uri; }
+        if let Some(verified) = args.requires_verified_renter { self.requires_verified_renter = verified; }
+        if let Some(deposit) = args.security_deposit { self.security_deposit = deposit; }
+        if let Some(mode) = args.booking_mode { self.booking_mode = mode; }
+        if let Some(policy) = args.cancellation_policy { self.cancellation_policy = policy; }
+    }
+
+    // Priced terms of the item, for pricing::quote
+    pub fn listing(&self) -> Listing<'_, PricingRule> {
+        Listing {
+            price_per_hour: self.price_per_hour,
+            price_per_day: self.price_per_day,
+            slot_minutes: self.slot_minutes,
+            price_per_slot: self.price_per_slot,
+            rules: &self.pricing_rules,
+            security_deposit: self.security_deposit,
         }
     }
 }
@@ -252,4 +1325,134 @@ pub enum ErrorCode {
     MetadataUriTooLong,
     #[msg("Price calculation resulted in an overflow.")]
     PriceCalculationOverflow,
-}
\ No newline at end of file
+    #[msg("Batch must contain between 1 and 10 entries.")]
+    InvalidBatchSize,
+    #[msg("Number of item accounts does not match the number of batch entries.")]
+    BatchAccountMismatch,
+    #[msg("Invalid item account supplied in batch.")]
+    InvalidItemAccount,
+    #[msg("Rating must be between 1 and 5.")]
+    InvalidRating,
+    #[msg("Rating aggregate overflowed.")]
+    RatingOverflow,
+    #[msg("Display name is too long. Max 32 characters allowed.")]
+    DisplayNameTooLong,
+    #[msg("Avatar URI is too long. Max 200 characters allowed.")]
+    AvatarUriTooLong,
+    #[msg("Unknown contact preference flags.")]
+    InvalidContactPreferences,
+    #[msg("Unknown item category.")]
+    InvalidCategory,
+    #[msg("A valid identity attestation is required to list in this category.")]
+    AttestationRequired,
+    #[msg("Attestation expiry must be in the future.")]
+    InvalidAttestationExpiry,
+    #[msg("Too many pricing rules. Max 8 allowed.")]
+    TooManyPricingRules,
+    #[msg("Invalid pricing rule.")]
+    InvalidPricingRule,
+    #[msg("Listing expiry must be in the future.")]
+    InvalidListingExpiry,
+    #[msg("Listing has not expired.")]
+    ListingNotExpired,
+    #[msg("Item has confirmed bookings that are not settled yet.")]
+    ItemHasActiveBookings,
+    #[msg("Registry still has registered items.")]
+    RegistryNotEmpty,
+    #[msg("Namespace must be between 1 and 32 characters.")]
+    InvalidNamespace,
+    #[msg("Item does not belong to this registry.")]
+    RegistryMismatch,
+    #[msg("Slots must be shorter than an hour and divide it evenly, with a non-zero price.")]
+    InvalidSlotPricing,
+    #[msg("Minimum price must not exceed the maximum price.")]
+    InvalidPriceBounds,
+    #[msg("Price is outside the range accepted by the registry.")]
+    PriceOutOfBounds,
+    #[msg("Rental nonce overflow.")]
+    RentalNonceOverflow,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: i64 = 3600;
+    const DAY: i64 = 24 * HOUR;
+    const SUNDAY_2024_01_07: i64 = 1_704_585_600;
+
+    #[test]
+    fn refund_bps_follows_the_notice_given() {
+        let start = 100 * DAY;
+        let flexible = CancellationPolicy::Flexible;
+        assert_eq!(flexible.refund_bps(start, start - 1), FULL_REFUND_BPS);
+        assert_eq!(flexible.refund_bps(start, start), 0);
+
+        let moderate = CancellationPolicy::Moderate;
+        assert_eq!(moderate.refund_bps(start, start - 48 * HOUR), FULL_REFUND_BPS);
+        assert_eq!(moderate.refund_bps(start, start - 48 * HOUR + 1), PARTIAL_REFUND_BPS);
+        assert_eq!(moderate.refund_bps(start, start - 1), PARTIAL_REFUND_BPS);
+        assert_eq!(moderate.refund_bps(start, start + HOUR), 0);
+
+        let strict = CancellationPolicy::Strict;
+        assert_eq!(strict.refund_bps(start, start - 7 * DAY), PARTIAL_REFUND_BPS);
+        assert_eq!(strict.refund_bps(start, start - 7 * DAY + 1), 0);
+    }
+
+    #[test]
+    fn refund_bps_saturates_extreme_times() {
+        assert_eq!(CancellationPolicy::Flexible.refund_bps(i64::MAX, i64::MIN), FULL_REFUND_BPS);
+        assert_eq!(CancellationPolicy::Flexible.refund_bps(i64::MIN, i64::MAX), 0);
+    }
+
+    #[test]
+    fn days_of_week_count_from_a_thursday_epoch() {
+        let thursday = RuleCondition::DaysOfWeek { mask: 1 << 4 };
+        assert!(thursday.matches(0));
+        assert!(thursday.matches(DAY - 1));
+        assert!(!thursday.matches(DAY));
+
+        let weekend = RuleCondition::DaysOfWeek { mask: 1 | 1 << 6 };
+        assert!(weekend.matches(SUNDAY_2024_01_07));
+        assert!(weekend.matches(SUNDAY_2024_01_07 - 1)); // Saturday night
+        assert!(!weekend.matches(SUNDAY_2024_01_07 + DAY)); // Monday
+    }
+
+    #[test]
+    fn days_of_week_before_the_epoch() {
+        // 1969-12-31 was a Wednesday
+        let wednesday = RuleCondition::DaysOfWeek { mask: 1 << 3 };
+        assert!(wednesday.matches(-1));
+        assert!(wednesday.matches(-DAY));
+        assert!(!wednesday.matches(-DAY - 1));
+        assert!(!wednesday.matches(0));
+    }
+
+    #[test]
+    fn date_range_is_half_open() {
+        let range = RuleCondition::DateRange { start: 10 * HOUR, end: 12 * HOUR };
+        assert!(!range.matches(10 * HOUR - 1));
+        assert!(range.matches(10 * HOUR));
+        assert!(range.matches(12 * HOUR - 1));
+        assert!(!range.matches(12 * HOUR));
+    }
+
+    #[test]
+    fn price_bounds_scale_with_mint_decimals() {
+        // 0.50 to 100.00 whole tokens
+        let bounds = PriceBounds { min: 50, max: 10_000 };
+        assert!(bounds.check(500_000, 6).is_ok());
+        assert!(bounds.check(100_000_000, 6).is_ok());
+        assert_eq!(bounds.check(499_999, 6), Err(ErrorCode::PriceOutOfBounds.into()));
+        assert_eq!(bounds.check(100_000_001, 6), Err(ErrorCode::PriceOutOfBounds.into()));
+        assert!(bounds.check(50, 2).is_ok());
+        assert_eq!(bounds.check(0, 0), Err(ErrorCode::PriceOutOfBounds.into()));
+    }
+
+    #[test]
+    fn unbounded_prices_accept_any_mint() {
+        assert!(PriceBounds::UNBOUNDED.check(0, 0).is_ok());
+        assert!(PriceBounds::UNBOUNDED.check(u64::MAX, 6).is_ok());
+        assert!(PriceBounds::UNBOUNDED.check(u64::MAX, u8::MAX).is_ok());
+    }
+}
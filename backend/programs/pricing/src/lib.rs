@@ -0,0 +1,541 @@
+// Rental pricing shared by item_registration, rental_flow and off-chain clients.
+// Kept free of Anchor and std so quotes shown to renters and amounts charged on-chain
+// always come from the same code.
+#![cfg_attr(not(test), no_std)]
+
+// Platform fee taken from each rental, in basis points (10%)
+pub const PLATFORM_FEE_BPS: u64 = 1_000;
+
+// Pricing rules are evaluated for every booked unit (hour or slot), so rule-priced rentals are
+// capped at 744 units: 31 days for hourly items, less for slot-priced items (e.g. 7.75 days of
+// 15-minute slots)
+pub const MAX_RULE_PRICED_UNITS: u64 = 24 * 31;
+
+const BPS_DENOMINATOR: u64 = 10_000;
+const SECONDS_PER_HOUR: i64 = 3600;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PricingError {
+    Overflow,
+    TooLongForPricingRules,
+    InvalidPrice, // Conversion price is not positive
+    InvalidShare, // Basis-point share above 100%
+}
+
+// Price change of a pricing rule for one matching hour
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Adjustment {
+    Percentage { bps: i16 },  // Basis points of the hour's base price
+    Absolute { amount: i64 }, // Base units added to (or subtracted from) the hour's price
+}
+
+// A time-based pricing rule, implemented by the on-chain rule type
+pub trait HourlyRule {
+    // True if the rule applies to the booked hour (or slot) starting at `unit_start`
+    fn applies_to(&self, unit_start: i64) -> bool;
+    fn adjustment(&self) -> Adjustment;
+}
+
+// The priced terms of a listing
+#[derive(Clone, Copy, Debug)]
+pub struct Listing<'a, R: HourlyRule> {
+    pub price_per_hour: u64,
+    pub price_per_day: u64,
+    pub slot_minutes: u16,     // Length of a booking slot; 0 books whole hours at the hourly/daily rates
+    pub price_per_slot: u64,   // Flat rate per slot when slot_minutes is set
+    pub rules: &'a [R],
+    pub security_deposit: u64, // Escrowed next to the price and returned after the rental
+}
+
+impl<R: HourlyRule> Listing<'_, R> {
+    // Length of one booking unit: a slot, or an hour for hourly items
+    pub fn unit_seconds(&self) -> i64 {
+        if self.slot_minutes == 0 {
+            SECONDS_PER_HOUR
+        } else {
+            self.slot_minutes as i64 * 60
+        }
+    }
+
+    // Length of a rental of `duration` booking units
+    pub fn duration_seconds(&self, duration: u64) -> Result<i64, PricingError> {
+        i64::try_from(duration).ok()
+            .and_then(|units| units.checked_mul(self.unit_seconds()))
+            .ok_or(PricingError::Overflow)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PromoDiscount {
+    Percentage { bps: u16 },
+    Fixed { amount: u64 },
+}
+
+// A promo redeemed for the rental. Platform-funded promos are paid for out of the platform fee
+// on the list price, so their discount is capped at that fee and never reduces the owner's payout;
+// owner-funded promos lower the price the fee is taken from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Promotion {
+    pub discount: PromoDiscount,
+    pub platform_funded: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PriceBreakdown {
+    pub base_price: u64,      // Per-slot rate, or the cheaper of hourly and daily-plus-hourly rates
+    pub rule_adjustment: i64, // Net change from pricing rules
+    pub list_price: u64,      // Base price plus rule adjustments, never below zero
+    pub discount: u64,        // Promo discount off the list price
+    pub total_price: u64,     // Rental price paid by the renter
+    pub platform_fee: u64,    // Taken out of total_price on completion
+    pub owner_payout: u64,    // total_price minus platform_fee
+    pub security_deposit: u64,
+    pub amount_due: u64,      // total_price plus security_deposit, escrowed at booking
+}
+
+// Prices a rental of `duration` booking units (hours, or slots for slot-priced items)
+// starting at `start_time`
+pub fn quote<R: HourlyRule>(
+    listing: &Listing<R>,
+    duration: u64,
+    start_time: i64,
+    promotion: Option<&Promotion>,
+) -> Result<PriceBreakdown, PricingError> {
+    let base_price = if listing.slot_minutes == 0 {
+        base_price(listing.price_per_hour, listing.price_per_day, duration)?
+    } else {
+        listing.price_per_slot.checked_mul(duration).ok_or(PricingError::Overflow)?
+    };
+    let rule_adjustment = rule_adjustment(listing.rules, base_price, duration, listing.unit_seconds(), start_time)?;
+
+    // Discounts can bring the price down to zero but not below
+    let list_price = (base_price as i128 + rule_adjustment as i128).max(0);
+    let list_price = u64::try_from(list_price).map_err(|_| PricingError::Overflow)?;
+
+    let discount = promotion.map_or(0, |promo| match promo.discount {
+        PromoDiscount::Percentage { bps } => ((list_price as u128) * (bps as u128) / BPS_DENOMINATOR as u128) as u64,
+        PromoDiscount::Fixed { amount } => amount.min(list_price),
+    });
+
+    let (discount, platform_fee) = if promotion.is_some_and(|promo| promo.platform_funded) {
+        let list_fee = fee_on(list_price)?;
+        let discount = discount.min(list_fee);
+        (discount, list_fee - discount)
+    } else {
+        (discount, fee_on(list_price - discount)?)
+    };
+    let total_price = list_price - discount;
+    let owner_payout = total_price - platform_fee;
+    let amount_due = total_price.checked_add(listing.security_deposit).ok_or(PricingError::Overflow)?;
+
+    Ok(PriceBreakdown {
+        base_price,
+        rule_adjustment,
+        list_price,
+        discount,
+        total_price,
+        platform_fee,
+        owner_payout,
+        security_deposit: listing.security_deposit,
+        amount_due,
+    })
+}
+
+// Hourly or daily-plus-hourly rate, whichever is cheaper
+pub fn base_price(price_per_hour: u64, price_per_day: u64, hours: u64) -> Result<u64, PricingError> {
+    let total_hour_price = price_per_hour.checked_mul(hours).ok_or(PricingError::Overflow)?;
+
+    let days = hours / 24;
+    let remaining_hours = hours % 24;
+
+    let daily_rate_total = price_per_day.checked_mul(days).ok_or(PricingError::Overflow)?;
+    let hourly_rate_for_remaining = price_per_hour.checked_mul(remaining_hours).ok_or(PricingError::Overflow)?;
+    let total_day_price = daily_rate_total.checked_add(hourly_rate_for_remaining).ok_or(PricingError::Overflow)?;
+
+    Ok(total_hour_price.min(total_day_price))
+}
+
+// Spreads the base price evenly over the booked units, then applies every matching rule to each
+// unit of [start_time, start_time + units * unit_seconds). Absolute adjustments are per hour, so
+// they are prorated for slots.
+fn rule_adjustment<R: HourlyRule>(
+    rules: &[R],
+    base_price: u64,
+    units: u64,
+    unit_seconds: i64,
+    start_time: i64,
+) -> Result<i64, PricingError> {
+    if rules.is_empty() || units == 0 {
+        return Ok(0);
+    }
+    if units > MAX_RULE_PRICED_UNITS {
+        return Err(PricingError::TooLongForPricingRules);
+    }
+
+    let unit_share = base_price / units;
+    let remainder = base_price % units;
+    let mut adjustment_total: i128 = 0;
+    for unit in 0..units {
+        let unit_start = start_time.checked_add(unit as i64 * unit_seconds).ok_or(PricingError::Overflow)?;
+        let unit_base = unit_share + u64::from(unit < remainder);
+        for rule in rules.iter().filter(|rule| rule.applies_to(unit_start)) {
+            adjustment_total += match rule.adjustment() {
+                Adjustment::Percentage { bps } => unit_base as i128 * bps as i128 / BPS_DENOMINATOR as i128,
+                Adjustment::Absolute { amount } => amount as i128 * unit_seconds as i128 / SECONDS_PER_HOUR as i128,
+            };
+        }
+    }
+    i64::try_from(adjustment_total).map_err(|_| PricingError::Overflow)
+}
+
+// Division of a rental's escrow (price and security deposit) between the parties
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Settlement {
+    pub renter_amount: u64, // Refunded share of the price plus the returned share of the deposit
+    pub owner_amount: u64,  // Owner's share of the price net of the platform fee, plus the kept deposit
+    pub platform_fee: u64,  // Platform fee on the owner's share of the price
+    pub price_paid: u64,    // Part of the price not refunded (owner share plus platform fee)
+}
+
+// Splits an escrow, refunding `renter_price_bps` of `total_price` and `renter_deposit_bps` of
+// `security_deposit` to the renter. The rest goes to the owner, and the platform keeps the part of
+// `platform_fee` matching the share of the price that was not refunded.
+pub fn split_escrow(
+    total_price: u64,
+    platform_fee: u64,
+    security_deposit: u64,
+    renter_price_bps: u16,
+    renter_deposit_bps: u16,
+) -> Result<Settlement, PricingError> {
+    let refunded_price = share_of(total_price, renter_price_bps)?;
+    let returned_deposit = share_of(security_deposit, renter_deposit_bps)?;
+    let price_paid = total_price - refunded_price;
+    let fee = if total_price == 0 {
+        0
+    } else {
+        (platform_fee.min(total_price) as u128 * price_paid as u128 / total_price as u128) as u64
+    };
+
+    Ok(Settlement {
+        renter_amount: refunded_price.checked_add(returned_deposit).ok_or(PricingError::Overflow)?,
+        owner_amount: (price_paid - fee).checked_add(security_deposit - returned_deposit).ok_or(PricingError::Overflow)?,
+        platform_fee: fee,
+        price_paid,
+    })
+}
+
+// `bps` basis points of `amount`, rounded down
+fn share_of(amount: u64, bps: u16) -> Result<u64, PricingError> {
+    if bps as u64 > BPS_DENOMINATOR {
+        return Err(PricingError::InvalidShare);
+    }
+    Ok((amount as u128 * bps as u128 / BPS_DENOMINATOR as u128) as u64)
+}
+
+// Late fee for a rental ending at `end_time` that was returned at `returned_at`. Once the return is
+// more than `grace_minutes` late, every started hour past `end_time` is charged at `fee_per_hour`.
+// The fee is drawn from the security deposit, so it is capped at the deposit.
+pub fn late_fee(end_time: i64, returned_at: i64, grace_minutes: u16, fee_per_hour: u64, security_deposit: u64) -> u64 {
+    let late_seconds = returned_at.saturating_sub(end_time);
+    if fee_per_hour == 0 || late_seconds <= grace_minutes as i64 * 60 {
+        return 0;
+    }
+    let late_hours = (late_seconds as u64).div_ceil(SECONDS_PER_HOUR as u64);
+    fee_per_hour.saturating_mul(late_hours).min(security_deposit)
+}
+
+fn fee_on(amount: u64) -> Result<u64, PricingError> {
+    Ok(amount.checked_mul(PLATFORM_FEE_BPS).ok_or(PricingError::Overflow)? / BPS_DENOMINATOR)
+}
+
+// Converts `amount` (base units of a token with `amount_decimals`) into base units of a token with
+// `target_decimals`, at an oracle price of `price * 10^expo` units of the first token per whole
+// target token. Rounds up, so conversions never fall short of the quoted amount.
+pub fn convert_at_price(amount: u64, amount_decimals: u8, price: i64, expo: i32, target_decimals: u8) -> Result<u64, PricingError> {
+    if price <= 0 {
+        return Err(PricingError::InvalidPrice);
+    }
+    let mut numerator = (amount as u128)
+        .checked_mul(pow10(target_decimals as u32)?)
+        .ok_or(PricingError::Overflow)?;
+    let mut denominator = (price as u128)
+        .checked_mul(pow10(amount_decimals as u32)?)
+        .ok_or(PricingError::Overflow)?;
+    if expo < 0 {
+        numerator = numerator.checked_mul(pow10(expo.unsigned_abs())?).ok_or(PricingError::Overflow)?;
+    } else {
+        denominator = denominator.checked_mul(pow10(expo as u32)?).ok_or(PricingError::Overflow)?;
+    }
+    u64::try_from(numerator.div_ceil(denominator)).map_err(|_| PricingError::Overflow)
+}
+
+fn pow10(exponent: u32) -> Result<u128, PricingError> {
+    10u128.checked_pow(exponent).ok_or(PricingError::Overflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: i64 = 3600;
+
+    // Applies to units starting in [from, until)
+    struct Rule {
+        from: i64,
+        until: i64,
+        adjustment: Adjustment,
+    }
+
+    impl HourlyRule for Rule {
+        fn applies_to(&self, unit_start: i64) -> bool {
+            unit_start >= self.from && unit_start < self.until
+        }
+
+        fn adjustment(&self) -> Adjustment {
+            self.adjustment
+        }
+    }
+
+    fn hourly(price_per_hour: u64, price_per_day: u64, rules: &[Rule]) -> Listing<'_, Rule> {
+        Listing { price_per_hour, price_per_day, slot_minutes: 0, price_per_slot: 0, rules, security_deposit: 0 }
+    }
+
+    fn promo(discount: PromoDiscount, platform_funded: bool) -> Promotion {
+        Promotion { discount, platform_funded }
+    }
+
+    #[test]
+    fn base_price_takes_cheaper_of_hourly_and_daily_rates() {
+        assert_eq!(base_price(10, 100, 30), Ok(160));
+        assert_eq!(base_price(10, 300, 30), Ok(300));
+        assert_eq!(base_price(10, 100, 0), Ok(0));
+        assert_eq!(base_price(u64::MAX, 0, 2), Err(PricingError::Overflow));
+    }
+
+    #[test]
+    fn quote_without_promo() {
+        let listing = Listing { security_deposit: 500, ..hourly(1_000, 20_000, &[]) };
+        let breakdown = quote(&listing, 3, 0, None).unwrap();
+        assert_eq!(breakdown, PriceBreakdown {
+            base_price: 3_000,
+            rule_adjustment: 0,
+            list_price: 3_000,
+            discount: 0,
+            total_price: 3_000,
+            platform_fee: 300,
+            owner_payout: 2_700,
+            security_deposit: 500,
+            amount_due: 3_500,
+        });
+    }
+
+    #[test]
+    fn quote_rounds_fee_and_percentage_discount_down() {
+        let listing = hourly(1_009, 0, &[]);
+        let breakdown = quote(&listing, 1, 0, None).unwrap();
+        assert_eq!(breakdown.platform_fee, 100);
+        assert_eq!(breakdown.owner_payout, 909);
+
+        let listing = hourly(999, 0, &[]);
+        let promotion = promo(PromoDiscount::Percentage { bps: 1_500 }, false);
+        let breakdown = quote(&listing, 1, 0, Some(&promotion)).unwrap();
+        assert_eq!(breakdown.discount, 149);
+        assert_eq!(breakdown.total_price, 850);
+        assert_eq!(breakdown.platform_fee, 85);
+    }
+
+    #[test]
+    fn owner_funded_promo_lowers_the_price_the_fee_is_taken_from() {
+        let listing = hourly(1_000, 0, &[]);
+        let promotion = promo(PromoDiscount::Percentage { bps: 1_000 }, false);
+        let breakdown = quote(&listing, 3, 0, Some(&promotion)).unwrap();
+        assert_eq!(breakdown.discount, 300);
+        assert_eq!(breakdown.total_price, 2_700);
+        assert_eq!(breakdown.platform_fee, 270);
+        assert_eq!(breakdown.owner_payout, 2_430);
+
+        // Fixed discounts larger than the price make the rental free
+        let promotion = promo(PromoDiscount::Fixed { amount: 5_000 }, false);
+        let breakdown = quote(&listing, 3, 0, Some(&promotion)).unwrap();
+        assert_eq!(breakdown.discount, 3_000);
+        assert_eq!(breakdown.total_price, 0);
+        assert_eq!(breakdown.platform_fee, 0);
+        assert_eq!(breakdown.owner_payout, 0);
+    }
+
+    #[test]
+    fn platform_funded_promo_comes_out_of_the_fee() {
+        let listing = hourly(1_000, 0, &[]);
+        let promotion = promo(PromoDiscount::Fixed { amount: 100 }, true);
+        let breakdown = quote(&listing, 3, 0, Some(&promotion)).unwrap();
+        assert_eq!(breakdown.discount, 100);
+        assert_eq!(breakdown.total_price, 2_900);
+        assert_eq!(breakdown.platform_fee, 200);
+        assert_eq!(breakdown.owner_payout, 2_700);
+
+        // Capped at the fee on the list price, so the owner's payout never drops
+        let promotion = promo(PromoDiscount::Percentage { bps: 5_000 }, true);
+        let breakdown = quote(&listing, 3, 0, Some(&promotion)).unwrap();
+        assert_eq!(breakdown.discount, 300);
+        assert_eq!(breakdown.total_price, 2_700);
+        assert_eq!(breakdown.platform_fee, 0);
+        assert_eq!(breakdown.owner_payout, 2_700);
+    }
+
+    #[test]
+    fn quote_of_zero_units_is_free() {
+        let listing = Listing { security_deposit: 500, ..hourly(1_000, 0, &[]) };
+        let breakdown = quote(&listing, 0, 0, None).unwrap();
+        assert_eq!(breakdown.total_price, 0);
+        assert_eq!(breakdown.platform_fee, 0);
+        assert_eq!(breakdown.amount_due, 500);
+    }
+
+    #[test]
+    fn quote_reports_overflow() {
+        assert_eq!(quote(&hourly(u64::MAX, 0, &[]), 2, 0, None), Err(PricingError::Overflow));
+
+        // The platform fee is computed before dividing, so it overflows for huge prices
+        let slots = Listing { slot_minutes: 15, price_per_slot: u64::MAX / 2, ..hourly(0, 0, &[]) };
+        assert_eq!(quote(&slots, 1, 0, None), Err(PricingError::Overflow));
+
+        let listing = Listing { security_deposit: u64::MAX, ..hourly(1, 0, &[]) };
+        assert_eq!(quote(&listing, 1, 0, None), Err(PricingError::Overflow));
+    }
+
+    #[test]
+    fn rules_apply_per_matching_hour() {
+        let rules = [
+            Rule { from: 0, until: 2 * HOUR, adjustment: Adjustment::Percentage { bps: 5_000 } },
+            Rule { from: 2 * HOUR, until: 3 * HOUR, adjustment: Adjustment::Absolute { amount: -100 } },
+        ];
+        let breakdown = quote(&hourly(1_000, 0, &rules), 4, 0, None).unwrap();
+        assert_eq!(breakdown.rule_adjustment, 900);
+        assert_eq!(breakdown.list_price, 4_900);
+        assert_eq!(breakdown.platform_fee, 490);
+    }
+
+    #[test]
+    fn rules_cannot_push_the_price_below_zero() {
+        let rules = [Rule { from: 0, until: HOUR, adjustment: Adjustment::Absolute { amount: -5_000 } }];
+        let breakdown = quote(&hourly(1_000, 0, &rules), 1, 0, None).unwrap();
+        assert_eq!(breakdown.rule_adjustment, -5_000);
+        assert_eq!(breakdown.list_price, 0);
+        assert_eq!(breakdown.total_price, 0);
+    }
+
+    #[test]
+    fn rule_adjustment_prorates_absolute_amounts_for_slots() {
+        let rules = [Rule { from: 0, until: HOUR, adjustment: Adjustment::Absolute { amount: 400 } }];
+        let slots = Listing { slot_minutes: 15, price_per_slot: 50, ..hourly(0, 0, &rules) };
+        let breakdown = quote(&slots, 6, 0, None).unwrap();
+        assert_eq!(breakdown.base_price, 300);
+        assert_eq!(breakdown.rule_adjustment, 400);
+        assert_eq!(breakdown.list_price, 700);
+    }
+
+    #[test]
+    fn rule_adjustment_spreads_the_remainder_over_the_first_units() {
+        // 10 over 3 hours is 4, 3, 3; halving only the first hour removes 2
+        let rules = [Rule { from: 0, until: HOUR, adjustment: Adjustment::Percentage { bps: -5_000 } }];
+        assert_eq!(rule_adjustment(&rules, 10, 3, HOUR, 0), Ok(-2));
+        let rules = [Rule { from: HOUR, until: 2 * HOUR, adjustment: Adjustment::Percentage { bps: -5_000 } }];
+        assert_eq!(rule_adjustment(&rules, 10, 3, HOUR, 0), Ok(-1));
+    }
+
+    #[test]
+    fn rule_priced_rentals_are_capped() {
+        let rules = [Rule { from: 0, until: HOUR, adjustment: Adjustment::Absolute { amount: 1 } }];
+        let limit = MAX_RULE_PRICED_UNITS;
+        assert!(quote(&hourly(1, 0, &rules), limit, 0, None).is_ok());
+        assert_eq!(quote(&hourly(1, 0, &rules), limit + 1, 0, None), Err(PricingError::TooLongForPricingRules));
+        assert!(quote(&hourly(1, 0, &[]), limit + 1, 0, None).is_ok());
+    }
+
+    #[test]
+    fn split_escrow_refunds_in_full() {
+        assert_eq!(split_escrow(3_000, 300, 500, 10_000, 10_000), Ok(Settlement {
+            renter_amount: 3_500,
+            owner_amount: 0,
+            platform_fee: 0,
+            price_paid: 0,
+        }));
+    }
+
+    #[test]
+    fn split_escrow_prorates_the_fee() {
+        assert_eq!(split_escrow(3_000, 300, 500, 5_000, 10_000), Ok(Settlement {
+            renter_amount: 2_000,
+            owner_amount: 1_350,
+            platform_fee: 150,
+            price_paid: 1_500,
+        }));
+
+        // Kept deposit goes to the owner
+        assert_eq!(split_escrow(3_000, 300, 500, 0, 0), Ok(Settlement {
+            renter_amount: 0,
+            owner_amount: 3_200,
+            platform_fee: 300,
+            price_paid: 3_000,
+        }));
+    }
+
+    #[test]
+    fn split_escrow_rounds_without_losing_funds() {
+        let settlement = split_escrow(1_001, 100, 0, 3_333, 10_000).unwrap();
+        assert_eq!(settlement.renter_amount, 333);
+        assert_eq!(settlement.price_paid, 668);
+        assert_eq!(settlement.platform_fee, 66);
+        assert_eq!(settlement.owner_amount, 602);
+        assert_eq!(settlement.renter_amount + settlement.owner_amount + settlement.platform_fee, 1_001);
+    }
+
+    #[test]
+    fn split_escrow_of_a_free_rental() {
+        let settlement = split_escrow(0, 0, 500, 5_000, 10_000).unwrap();
+        assert_eq!(settlement.renter_amount, 500);
+        assert_eq!(settlement.owner_amount, 0);
+        assert_eq!(settlement.platform_fee, 0);
+    }
+
+    #[test]
+    fn split_escrow_rejects_shares_above_100_percent() {
+        assert_eq!(split_escrow(3_000, 300, 500, 10_001, 10_000), Err(PricingError::InvalidShare));
+        assert_eq!(split_escrow(3_000, 300, 500, 10_000, 10_001), Err(PricingError::InvalidShare));
+    }
+
+    #[test]
+    fn late_fee_charges_started_hours_after_the_grace_period() {
+        let end = 100 * HOUR;
+        assert_eq!(late_fee(end, end - HOUR, 15, 50, 1_000), 0);
+        assert_eq!(late_fee(end, end + 15 * 60, 15, 50, 1_000), 0);
+        assert_eq!(late_fee(end, end + 15 * 60 + 1, 15, 50, 1_000), 50);
+        assert_eq!(late_fee(end, end + HOUR + 1, 15, 50, 1_000), 100);
+        assert_eq!(late_fee(end, end + 1, 0, 50, 1_000), 50);
+        assert_eq!(late_fee(end, end + 10 * HOUR, 0, 0, 1_000), 0);
+    }
+
+    #[test]
+    fn late_fee_is_capped_at_the_deposit() {
+        assert_eq!(late_fee(0, 100 * HOUR, 0, 50, 1_000), 1_000);
+        assert_eq!(late_fee(0, i64::MAX, 0, u64::MAX, 1_000), 1_000);
+        assert_eq!(late_fee(0, 100 * HOUR, 0, 50, 0), 0);
+    }
+
+    #[test]
+    fn convert_at_price_rounds_up() {
+        // $150 per SOL with a Pyth-style exponent, 6-decimal USD into lamports
+        assert_eq!(convert_at_price(150_000_000, 6, 15_000_000_000, -8, 9), Ok(1_000_000_000));
+        assert_eq!(convert_at_price(1, 6, 15_000_000_000, -8, 9), Ok(7));
+        assert_eq!(convert_at_price(150_000_000, 6, 15, 1, 9), Ok(1_000_000_000));
+        assert_eq!(convert_at_price(0, 6, 15_000_000_000, -8, 9), Ok(0));
+    }
+
+    #[test]
+    fn convert_at_price_rejects_bad_prices_and_overflow() {
+        assert_eq!(convert_at_price(1, 6, 0, -8, 9), Err(PricingError::InvalidPrice));
+        assert_eq!(convert_at_price(1, 6, -1, -8, 9), Err(PricingError::InvalidPrice));
+        assert_eq!(convert_at_price(1, 6, 1, -39, 9), Err(PricingError::Overflow));
+        assert_eq!(convert_at_price(u64::MAX, 0, 1, 0, 9), Err(PricingError::Overflow));
+    }
+}
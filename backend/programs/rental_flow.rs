@@ -1,11 +1,20 @@
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::clock::Clock;
-use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+use anchor_lang::system_program;
+use anchor_spl::token::{self, spl_token, Mint, SyncNative, Token, TokenAccount, Transfer};
 use anchor_spl::associated_token::AssociatedToken;
-use item_registration::ItemAccount;
-use item_registration::cpi::accounts::SetItemAvailabilityCpiAccounts as ItemRegSetAvailabilityAccounts;
-use item_registration::cpi::set_item_availability as item_reg_set_availability;
+use item_registration::{Attestation, BookingMode, CancellationPolicy, ItemAccount, ItemRegistry, ProfileRole, RentalActivity};
+use item_registration::{ATTESTATION_SEED, FULL_REFUND_BPS, RENTAL_AUTHORITY_SEED, USER_PROFILE_SEED};
+use item_registration::cpi::accounts::RecordRentalActivity as ItemRegRecordRentalActivityAccounts;
+use item_registration::cpi::accounts::RecordRating as ItemRegRecordRatingAccounts;
+use item_registration::cpi::accounts::RecordProfileRental as ItemRegRecordProfileRentalAccounts;
+use item_registration::cpi::accounts::ClaimRentalNonce as ItemRegClaimRentalNonceAccounts;
+use item_registration::cpi::record_rental_activity as item_reg_record_rental_activity;
+use item_registration::cpi::record_rating as item_reg_record_rating;
+use item_registration::cpi::record_profile_rental as item_reg_record_profile_rental;
+use item_registration::cpi::claim_rental_nonce as item_reg_claim_rental_nonce;
 use item_registration::program::ItemRegistration as ItemRegistrationProgram;
+use pricing::{PriceBreakdown, PricingError, PromoDiscount, Promotion, Settlement};
 use std::str::FromStr;
 
 // USDC mint address on Devnet
@@ -17,36 +26,231 @@ declare_id!("6XqPznLJiGdqzD4FkD9yQGMN2XQb1fLXL1UKfwq8kgPQ");
 // Add a constant for the system (platform) revenue address (replace with your real address)
 const SYSTEM_REVENUE_ADDRESS: &str = "6YDGTnmBDe34SYeziSbsVP6ss5ogWREHXec87CJu7Hos";
 
+// Promo codes are used as PDA seeds, so they are limited to the max seed length
+const MAX_PROMO_CODE_LENGTH: usize = 32;
+
+// Holds reserve an item slot between choosing dates and paying, against a refundable bond
+const HOLD_DURATION_SECONDS: i64 = 5 * 60;
+const HOLD_BOND_LAMPORTS: u64 = 10_000_000; // 0.01 SOL
+
+// Owners of request-to-book items must answer a request within a day, and before the rental starts
+const REQUEST_RESPONSE_SECONDS: i64 = 24 * 60 * 60;
+
+// Max reserved rentals (pending, upcoming or running) per item; windows are freed when settled
+const MAX_SCHEDULED_BOOKINGS: usize = 32;
+
+// Max length for a condition report photo-bundle URI (also used for dispute evidence)
+const MAX_REPORT_URI_LENGTH: usize = 200;
+
+// Evidence entries each dispute can hold, across both parties
+const MAX_DISPUTE_EVIDENCE: usize = 8;
+
+// SOL payments convert USD prices at the SOL/USD feed price, which must be recent and precise
+const MAX_PRICE_AGE_SECONDS: i64 = 60;
+const MAX_PRICE_CONFIDENCE_BPS: u128 = 100; // Confidence interval at most 1% of the price
+
+// Pyth v2 price account layout (a local mock account with the same layout works in tests)
+const PYTH_MAGIC: u32 = 0xa1b2_c3d4;
+const PYTH_PRICE_ACCOUNT_TYPE: u32 = 3;
+const PYTH_STATUS_TRADING: u32 = 1;
+const PYTH_EXPO_OFFSET: usize = 20;
+const PYTH_TIMESTAMP_OFFSET: usize = 96;
+const PYTH_AGG_PRICE_OFFSET: usize = 208;
+const PYTH_AGG_CONF_OFFSET: usize = 216;
+const PYTH_AGG_STATUS_OFFSET: usize = 224;
+
 #[program]
 pub mod rental_flow {
     use super::*;
 
-    pub fn initiate_rental(
-        ctx: Context<InitiateRental>,
-        hours: u64,
-        start_time: i64,
+    // Creates the platform configuration of a marketplace. Only the authority of the
+    // marketplace's item registry may call this.
+    pub fn initialize_config(ctx: Context<InitializeConfig>, admin: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.platform_config;
+        config.registry = ctx.accounts.item_registry.key();
+        config.admin = admin;
+        config.bump = ctx.bumps.platform_config;
+        Ok(())
+    }
+
+    // `sol_usd_feed` and `usd_mint` enable SOL payments for items priced in `usd_mint`;
+    // setting the feed to the default pubkey disables them. Disputes can only be opened while an
+    // `arbiter` is set.
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        new_admin: Option<Pubkey>,
+        sol_usd_feed: Option<Pubkey>,
+        usd_mint: Option<Pubkey>,
+        arbiter: Option<Pubkey>,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.platform_config;
+        if let Some(admin) = new_admin { config.admin = admin; }
+        if let Some(feed) = sol_usd_feed { config.sol_usd_feed = feed; }
+        if let Some(mint) = usd_mint { config.usd_mint = mint; }
+        if let Some(arbiter) = arbiter { config.arbiter = arbiter; }
+        Ok(())
+    }
+
+    // Creates a promo code. Promos created by the platform admin are funded out of the platform
+    // fee and may be scoped freely; promos created by anyone else only apply to the creator's items.
+    pub fn create_promo(
+        ctx: Context<CreatePromo>,
+        code: String,
+        discount: Discount,
+        expires_at: i64,
+        max_uses: u32,
+        item: Option<Pubkey>,
+        owner: Option<Pubkey>,
     ) -> Result<()> {
-        // Input validation
-        require!(start_time >= Clock::get()?.unix_timestamp, ErrorCode::InvalidStartTime);
-        require!(hours > 0, ErrorCode::InvalidRentalDuration);
+        require!(!code.is_empty() && code.len() <= MAX_PROMO_CODE_LENGTH, ErrorCode::InvalidPromoCode);
+        require!(expires_at > Clock::get()?.unix_timestamp, ErrorCode::InvalidPromo);
+        require!(max_uses > 0, ErrorCode::InvalidPromo);
+        match discount {
+            Discount::Percentage { bps } => require!(bps > 0 && bps <= 10_000, ErrorCode::InvalidPromo),
+            Discount::Fixed { amount } => require!(amount > 0, ErrorCode::InvalidPromo),
+        }
 
-        // Item availability check
-        require!(ctx.accounts.item_account.is_available, ErrorCode::ItemNotAvailable);
+        let creator = ctx.accounts.creator.key();
+        let platform_funded = creator == ctx.accounts.platform_config.admin;
 
-        let rental = &mut ctx.accounts.rental_transaction;
+        let promo = &mut ctx.accounts.promo;
+        promo.registry = ctx.accounts.platform_config.registry;
+        promo.creator = creator;
+        promo.code = code;
+        promo.discount = discount;
+        promo.expires_at = expires_at;
+        promo.max_uses = max_uses;
+        promo.uses = 0;
+        promo.item = item;
+        // Owner promos are always limited to the creator's own items
+        promo.owner = if platform_funded { owner } else { Some(creator) };
+        promo.platform_funded = platform_funded;
+        promo.bump = ctx.bumps.promo;
+        Ok(())
+    }
+
+    // Lets the creator retire a promo code and reclaim its rent
+    pub fn close_promo(_ctx: Context<ClosePromo>) -> Result<()> {
+        Ok(())
+    }
+
+    // Creates the item's booking schedule, which quote_rental reads. Anyone may open it (the API
+    // adds it to the registration transaction); otherwise the item's first booking creates it.
+    pub fn open_booking_schedule(ctx: Context<OpenBookingSchedule>) -> Result<()> {
+        ctx.accounts.booking_schedule.open(ctx.accounts.item_account.key(), ctx.bumps.booking_schedule);
+        Ok(())
+    }
+
+    // Reserves a slot on an item for HOLD_DURATION_SECONDS. The bond and rent are returned to the
+    // holder when the hold is consumed by their initiate_rental or released. An expired hold still
+    // occupying the item's hold PDA is replaced, refunding its holder (passed as `previous_holder`).
+    pub fn place_hold(ctx: Context<PlaceHold>, duration: u64, start_time: i64) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(start_time >= now, ErrorCode::InvalidStartTime);
+        require!(duration > 0, ErrorCode::InvalidRentalDuration);
+        require_listed(&ctx.accounts.item_account, now)?;
+
+        let hold_info = ctx.accounts.hold.to_account_info();
+        if !hold_info.data_is_empty() {
+            let previous = Hold::try_deserialize(&mut &hold_info.try_borrow_data()?[..])?;
+            require!(now >= previous.expires_at, ErrorCode::ItemOnHold);
+            let previous_holder = ctx.accounts.previous_holder.as_ref().ok_or(ErrorCode::Unauthorized)?;
+            require_keys_eq!(previous_holder.key(), previous.holder, ErrorCode::Unauthorized);
+            close_program_account(&hold_info, previous_holder)?;
+        }
+
+        // Allocate the hold account, owned by this program
+        let item_key = ctx.accounts.item_account.key();
+        let bump = [ctx.bumps.hold];
+        let seeds: &[&[u8]] = &[b"hold".as_ref(), item_key.as_ref(), &bump];
+        create_program_account(
+            &ctx.accounts.holder.to_account_info(),
+            &hold_info,
+            &ctx.accounts.system_program,
+            Hold::LEN,
+            seeds,
+        )?;
+
+        // Transfer the bond from the holder to the hold account
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.holder.to_account_info(),
+                    to: hold_info.clone(),
+                },
+            ),
+            HOLD_BOND_LAMPORTS,
+        )?;
+
+        let duration_seconds = ctx.accounts.item_account.listing().duration_seconds(duration).map_err(pricing_error)?;
+        let hold = Hold {
+            holder: ctx.accounts.holder.key(),
+            item: item_key,
+            start_time,
+            end_time: start_time.checked_add(duration_seconds).ok_or(ErrorCode::ArithmeticOverflow)?,
+            expires_at: now.checked_add(HOLD_DURATION_SECONDS).ok_or(ErrorCode::ArithmeticOverflow)?,
+            bond: HOLD_BOND_LAMPORTS,
+            bump: ctx.bumps.hold,
+        };
+        let mut data = hold_info.try_borrow_mut_data()?;
+        let mut writer: &mut [u8] = &mut data;
+        hold.try_serialize(&mut writer)?;
+        Ok(())
+    }
+
+    // Closes a hold, refunding bond and rent to the holder. The holder may release at any time;
+    // anyone may release an expired hold so the item can be held again.
+    pub fn release_hold(ctx: Context<ReleaseHold>) -> Result<()> {
+        let hold = &ctx.accounts.hold;
+        let is_holder = ctx.accounts.caller.key() == hold.holder;
+        require!(is_holder || Clock::get()?.unix_timestamp >= hold.expires_at, ErrorCode::HoldNotExpired);
+        Ok(())
+    }
+
+    // Prices a rental without changing any state. Performs the same checks as initiate_rental and
+    // returns the breakdown as return data (Anchor calls set_return_data), so clients can quote by
+    // simulating this instruction.
+    pub fn quote_rental(ctx: Context<QuoteRental>, duration: u64, start_time: i64) -> Result<RentalQuote> {
         let item = &ctx.accounts.item_account;
-        let total_price = calculate_total_price(item.price_per_hour, item.price_per_day, hours)?;
-        rental.item = item.key();
-        rental.renter = ctx.accounts.renter.key();
-        rental.owner = ctx.accounts.owner.key();
-        rental.start_time = start_time;
-        let duration_seconds = (hours as i64).checked_mul(3600).ok_or(ErrorCode::ArithmeticOverflow)?;
-        rental.end_time = start_time.checked_add(duration_seconds).ok_or(ErrorCode::ArithmeticOverflow)?;
-        rental.total_price = total_price;
-        rental.is_active = true;
-        rental.is_completed = false;
+        let renter = ctx.accounts.renter.key();
+        let end_time = validate_booking(item, &renter, ctx.accounts.attestation.as_deref(), duration, start_time)?;
+        check_hold(&ctx.accounts.hold, &renter, start_time, end_time)?;
+        ctx.accounts.booking_schedule.check_available(start_time, end_time)?;
 
-        // Transfer USDC from renter to escrow
+        let promotion = match ctx.accounts.promo.as_ref() {
+            Some(promo) => Some(promo.promotion(item, Clock::get()?.unix_timestamp)?),
+            None => None,
+        };
+        let price = pricing::quote(&item.listing(), duration, start_time, promotion.as_ref()).map_err(pricing_error)?;
+        Ok(RentalQuote::new(start_time, end_time, price))
+    }
+
+    // Books the item for `duration` units starting at `start_time`. Units are hours, or the
+    // item's booking slots if it is slot-priced.
+    pub fn initiate_rental(
+        ctx: Context<InitiateRental>,
+        duration: u64,
+        start_time: i64,
+    ) -> Result<()> {
+        let booking = Booking {
+            renter: &ctx.accounts.renter,
+            owner: &ctx.accounts.owner,
+            item_account: &ctx.accounts.item_account,
+            item_registry: &ctx.accounts.item_registry,
+            rental_transaction: &mut ctx.accounts.rental_transaction,
+            booking_schedule: &mut ctx.accounts.booking_schedule,
+            booking_schedule_bump: ctx.bumps.booking_schedule,
+            attestation: ctx.accounts.attestation.as_deref(),
+            promo: ctx.accounts.promo.as_mut(),
+            hold: &ctx.accounts.hold,
+            rental_authority: &ctx.accounts.rental_authority,
+            rental_authority_bump: ctx.bumps.rental_authority,
+            item_registration_program: &ctx.accounts.item_registration_program,
+        };
+        let amount_due = booking.book(duration, start_time, ctx.accounts.usdc_mint.key(), Ok)?;
+
+        // Transfer the price and the security deposit from the renter to escrow
         let cpi_accounts = Transfer {
             from: ctx.accounts.renter_usdc.to_account_info(),
             to: ctx.accounts.escrow_usdc.to_account_info(),
@@ -54,21 +258,85 @@ pub mod rental_flow {
         };
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        token::transfer(cpi_ctx, total_price)?;
+        token::transfer(cpi_ctx, amount_due)
+    }
 
-        // CPI to item_registration to set item as unavailable
-        let cpi_program = ctx.accounts.item_registration_program.to_account_info();
-        let cpi_accounts = ItemRegSetAvailabilityAccounts {
-            item_account: ctx.accounts.item_account.to_account_info(),
-            owner: ctx.accounts.owner.to_account_info(), // Owner of the item_account
+    // Books a USD-priced item paying in SOL. Prices are converted at the marketplace's SOL/USD
+    // feed and the lamports are escrowed as wrapped SOL, so completion and cancellation work as
+    // for token payments (with wrapped SOL accounts). Fails if the converted amount due exceeds
+    // `max_lamports`.
+    pub fn initiate_rental_with_sol(
+        ctx: Context<InitiateRentalWithSol>,
+        duration: u64,
+        start_time: i64,
+        max_lamports: u64,
+    ) -> Result<()> {
+        // Convert every amount into lamports at the current SOL/USD price
+        let (sol_price, expo) = read_sol_usd_price(&ctx.accounts.price_feed, Clock::get()?.unix_timestamp)?;
+        let price_decimals = ctx.accounts.item_account.price_decimals;
+        let sol_decimals = ctx.accounts.native_mint.decimals;
+        let to_lamports = |amount: u64| {
+            pricing::convert_at_price(amount, price_decimals, sol_price, expo, sol_decimals).map_err(pricing_error)
         };
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        item_reg_set_availability(cpi_ctx, false)?; // false for unavailable
 
-        Ok(())
+        let booking = Booking {
+            renter: &ctx.accounts.renter,
+            owner: &ctx.accounts.owner,
+            item_account: &ctx.accounts.item_account,
+            item_registry: &ctx.accounts.item_registry,
+            rental_transaction: &mut ctx.accounts.rental_transaction,
+            booking_schedule: &mut ctx.accounts.booking_schedule,
+            booking_schedule_bump: ctx.bumps.booking_schedule,
+            attestation: ctx.accounts.attestation.as_deref(),
+            promo: ctx.accounts.promo.as_mut(),
+            hold: &ctx.accounts.hold,
+            rental_authority: &ctx.accounts.rental_authority,
+            rental_authority_bump: ctx.bumps.rental_authority,
+            item_registration_program: &ctx.accounts.item_registration_program,
+        };
+        let amount_due = booking.book(duration, start_time, ctx.accounts.native_mint.key(), to_lamports)?;
+        require!(amount_due <= max_lamports, ErrorCode::SlippageExceeded);
+
+        // Move the lamports into the wrapped SOL escrow and sync its token balance
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.renter.to_account_info(),
+                    to: ctx.accounts.escrow_wsol.to_account_info(),
+                },
+            ),
+            amount_due,
+        )?;
+        token::sync_native(CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            SyncNative { account: ctx.accounts.escrow_wsol.to_account_info() },
+        ))
+    }
+
+    // Permissionless. Marks a confirmed rental as in progress once its start time has passed, so
+    // the registry's currently-rented count covers running rentals but not future reservations.
+    pub fn start_rental(ctx: Context<StartRental>) -> Result<()> {
+        let rental = &mut ctx.accounts.rental_transaction;
+        require!(rental.status == RentalStatus::Active && !rental.started, ErrorCode::InvalidRentalState);
+        require!(Clock::get()?.unix_timestamp >= rental.start_time, ErrorCode::RentalNotStarted);
+        rental.started = true;
+
+        // Update marketplace statistics
+        record_rental_activity(
+            &ctx.accounts.item_registration_program,
+            &ctx.accounts.item_account,
+            &ctx.accounts.item_registry,
+            &ctx.accounts.rental_authority,
+            ctx.bumps.rental_authority,
+            RentalActivity::Started,
+        )
     }
 
-    pub fn complete_rental(ctx: Context<CompleteRental>) -> Result<()> {
+    // `returned_at` is the return time confirmed by the owner; late fees are charged up to it, or
+    // up to the renter's recorded return if that is earlier. Parties who disagree on the return
+    // time should open a dispute instead of completing.
+    pub fn complete_rental(ctx: Context<CompleteRental>, returned_at: i64) -> Result<()> {
         // Validate system_usdc account owner matches SYSTEM_REVENUE_ADDRESS
         let expected_system_pubkey = Pubkey::from_str(SYSTEM_REVENUE_ADDRESS).map_err(|_| ErrorCode::InvalidSystemAccount)?;
         require_keys_eq!(ctx.accounts.system_usdc.owner, expected_system_pubkey, ErrorCode::InvalidSystemAccount);
@@ -77,16 +345,38 @@ pub mod rental_flow {
 
         // Pre-read immutable data from rental_transaction before mutable borrow
         let renter_key_for_pda_val = ctx.accounts.rental_transaction.renter;
+        let nonce_for_pda = ctx.accounts.rental_transaction.nonce.to_le_bytes();
         let rental_transaction_account_info = ctx.accounts.rental_transaction.to_account_info();
         let total_price_val = ctx.accounts.rental_transaction.total_price;
+        let system_fee = ctx.accounts.rental_transaction.platform_fee;
+        let security_deposit = ctx.accounts.rental_transaction.security_deposit;
 
         let rental = &mut ctx.accounts.rental_transaction;
-        require!(rental.is_active && !rental.is_completed, ErrorCode::InvalidRentalState);
-        require!(Clock::get()?.unix_timestamp >= rental.end_time, ErrorCode::RentalNotYetConcluded);
+        require!(rental.status == RentalStatus::Active, ErrorCode::InvalidRentalState);
+        let now = Clock::get()?.unix_timestamp;
+        require!(now >= rental.end_time, ErrorCode::RentalNotYetConcluded);
+        require!(
+            returned_at >= rental.start_time && returned_at <= now,
+            ErrorCode::InvalidReturnTime
+        );
+
+        // Late returns are charged from the security deposit, up to the owner's return time or
+        // the renter's recorded return, whichever is earlier
+        let returned_at = if rental.returned_at != 0 { returned_at.min(rental.returned_at) } else { returned_at };
+        let late_fee = pricing::late_fee(
+            rental.end_time,
+            returned_at,
+            rental.late_grace_minutes,
+            rental.late_fee_per_hour,
+            security_deposit,
+        );
+        rental.late_fee = late_fee;
 
-        // Calculate platform fee and owner amount (10% to system, 90% to owner)
-        let system_fee = total_price_val.checked_mul(10).ok_or(ErrorCode::ArithmeticOverflow)?.checked_div(100).ok_or(ErrorCode::ArithmeticOverflow)?;
-        let owner_amount = total_price_val.checked_sub(system_fee).ok_or(ErrorCode::ArithmeticOverflow)?;
+        // Owner receives the amount paid minus the platform fee fixed at booking, plus any late fee
+        let owner_amount = total_price_val
+            .checked_sub(system_fee)
+            .and_then(|amount| amount.checked_add(late_fee))
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
 
         // Seeds for the rental_transaction PDA, which is the authority for escrow_usdc
         let item_key_for_pda = ctx.accounts.item_account.key(); // Key used in PDA seeds definition for rental_transaction
@@ -95,11 +385,12 @@ pub mod rental_flow {
             b"rental_transaction".as_ref(),
             item_key_for_pda.as_ref(),
             renter_key_for_pda_val.as_ref(), // Use pre-read value
+            nonce_for_pda.as_ref(),
             &[bump],
         ];
         let signer_seeds = &[&seeds[..]];
 
-        // Transfer 90% USDC from escrow_usdc to owner_usdc
+        // Transfer the owner's share from escrow_usdc to owner_usdc
         token::transfer(
             CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
@@ -113,7 +404,7 @@ pub mod rental_flow {
             owner_amount,
         )?;
 
-        // Transfer 10% USDC from escrow_usdc to system_usdc
+        // Transfer the platform fee from escrow_usdc to system_usdc
         token::transfer(
             CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
@@ -127,6 +418,23 @@ pub mod rental_flow {
             system_fee,
         )?;
 
+        // Return the security deposit, minus any late fee, to the renter
+        let deposit_refund = security_deposit - late_fee;
+        if deposit_refund > 0 {
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    token::Transfer {
+                        from: ctx.accounts.escrow_usdc.to_account_info(),
+                        to: ctx.accounts.renter_usdc.to_account_info(),
+                        authority: rental_transaction_account_info.clone(),
+                    },
+                    signer_seeds,
+                ),
+                deposit_refund,
+            )?;
+        }
+
         // Close the escrow USDC account, funds go to owner
         token::close_account(
             CpiContext::new_with_signer(
@@ -140,79 +448,413 @@ pub mod rental_flow {
             )
         )?;
 
-        rental.is_active = false;
-        rental.is_completed = true;
+        rental.status = RentalStatus::Completed;
 
-        // CPI to item_registration to set item as available
-        let cpi_program = ctx.accounts.item_registration_program.to_account_info();
-        let cpi_accounts = ItemRegSetAvailabilityAccounts {
-            item_account: ctx.accounts.item_account.to_account_info(),
-            owner: ctx.accounts.owner.to_account_info(),
-        };
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        item_reg_set_availability(cpi_ctx, true)?; // true for available
+        // Free the rental window in the item's schedule
+        let rental_key = ctx.accounts.rental_transaction.key();
+        ctx.accounts.booking_schedule.release(&rental_key);
+
+        // Update marketplace statistics
+        record_rental_activity(
+            &ctx.accounts.item_registration_program,
+            &ctx.accounts.item_account,
+            &ctx.accounts.item_registry,
+            &ctx.accounts.rental_authority,
+            ctx.bumps.rental_authority,
+            RentalActivity::Completed {
+                mint: ctx.accounts.usdc_mint.key(),
+                amount: total_price_val,
+                started: ctx.accounts.rental_transaction.started,
+            },
+        )?;
+
+        // Update rental counters on the participants' profiles, if they have one
+        record_profile_rental(
+            &ctx.accounts.item_registration_program,
+            &ctx.accounts.owner_profile,
+            &ctx.accounts.rental_authority,
+            ctx.bumps.rental_authority,
+            ProfileRole::Owner,
+        )?;
+        record_profile_rental(
+            &ctx.accounts.item_registration_program,
+            &ctx.accounts.renter_profile,
+            &ctx.accounts.rental_authority,
+            ctx.bumps.rental_authority,
+            ProfileRole::Renter,
+        )?;
 
         Ok(())
     }
 
+    // Cancels an active rental. A renter cancelling alone must do so before the rental starts and
+    // is refunded according to the cancellation policy recorded at booking, and the owner keeps the
+    // rest of the price (net of the platform fee on it); if the owner co-signs, the renter is
+    // refunded in full. The security deposit is always returned. A rental that has started is
+    // settled by complete_rental or a dispute instead.
     pub fn cancel_rental(ctx: Context<CancelRental>) -> Result<()> {
-        // Pre-read immutable data from rental_transaction before mutable borrow
-        let rental_transaction_account_info = ctx.accounts.rental_transaction.to_account_info();
-        let total_price_val = ctx.accounts.rental_transaction.total_price;
+        let expected_system_pubkey = Pubkey::from_str(SYSTEM_REVENUE_ADDRESS).map_err(|_| ErrorCode::InvalidSystemAccount)?;
+        require_keys_eq!(ctx.accounts.system_usdc.owner, expected_system_pubkey, ErrorCode::InvalidSystemAccount);
+
+        let rental = &ctx.accounts.rental_transaction;
+        require!(rental.status == RentalStatus::Active, ErrorCode::InvalidRentalState);
+        let refund_bps = match &ctx.accounts.owner {
+            Some(owner) => {
+                require_keys_eq!(owner.key(), rental.owner, ErrorCode::Unauthorized);
+                FULL_REFUND_BPS
+            }
+            None => {
+                let now = Clock::get()?.unix_timestamp;
+                require!(now < rental.start_time, ErrorCode::RentalAlreadyStarted);
+                rental.cancellation_policy.refund_bps(rental.start_time, now)
+            }
+        };
+        let settlement = pricing::split_escrow(
+            rental.total_price,
+            rental.platform_fee,
+            rental.security_deposit,
+            refund_bps,
+            FULL_REFUND_BPS,
+        ).map_err(pricing_error)?;
 
+        // Pay out the escrow; its rent goes back to the renter, who paid for it
+        settle_escrow(
+            rental,
+            ctx.bumps.rental_transaction,
+            &ctx.accounts.escrow_usdc,
+            &[
+                (&ctx.accounts.renter_usdc, settlement.renter_amount),
+                (&ctx.accounts.owner_usdc, settlement.owner_amount),
+                (&ctx.accounts.system_usdc, settlement.platform_fee),
+            ],
+            &ctx.accounts.renter,
+            &ctx.accounts.token_program,
+        )?;
+        ctx.accounts.rental_transaction.status = RentalStatus::Cancelled;
+
+        // Free the rental window in the item's schedule
+        let rental_key = ctx.accounts.rental_transaction.key();
+        ctx.accounts.booking_schedule.release(&rental_key);
+
+        // The booking did not go ahead, so the promo can be used again
+        if let Some(promo) = ctx.accounts.promo.as_mut() {
+            promo.restore_use();
+        }
+
+        // Update marketplace statistics; a cancelled booking is never a completed rental, even
+        // when the policy lets the owner keep part of the price
+        record_rental_activity(
+            &ctx.accounts.item_registration_program,
+            &ctx.accounts.item_account,
+            &ctx.accounts.item_registry,
+            &ctx.accounts.rental_authority,
+            ctx.bumps.rental_authority,
+            RentalActivity::Cancelled { started: ctx.accounts.rental_transaction.started },
+        )?;
+
+        Ok(())
+    }
+
+    // Accepts a pending request for a request-to-book item, confirming the booking
+    pub fn accept_rental_request(ctx: Context<AcceptRentalRequest>) -> Result<()> {
         let rental = &mut ctx.accounts.rental_transaction;
-        require!(rental.is_active && !rental.is_completed, ErrorCode::InvalidRentalState);
+        require!(rental.status == RentalStatus::Requested, ErrorCode::InvalidRentalState);
+        let now = Clock::get()?.unix_timestamp;
+        require!(now <= rental.respond_by, ErrorCode::RentalRequestExpired);
+        require_listed(&ctx.accounts.item_account, now)?;
+        rental.status = RentalStatus::Active;
 
-        // Seeds for the rental_transaction PDA, which is the authority for escrow_usdc
-        let item_key_for_pda = ctx.accounts.item_account.key(); // Key used in PDA seeds definition for rental_transaction
-        let renter_key_for_pda = ctx.accounts.renter.key(); // Renter signer's key used in PDA seeds definition
-        let bump = ctx.bumps.rental_transaction; // Bump for the rental_transaction PDA
-        let seeds = &[
-            b"rental_transaction".as_ref(),
-            item_key_for_pda.as_ref(),
-            renter_key_for_pda.as_ref(), // This remains as is, not from rental_transaction data
-            &[bump],
-        ];
-        let signer_seeds = &[&seeds[..]];
+        confirm_booking(
+            &ctx.accounts.item_registration_program,
+            &ctx.accounts.item_account,
+            &ctx.accounts.item_registry,
+            &ctx.accounts.rental_authority,
+            ctx.bumps.rental_authority,
+        )
+    }
 
-        // Transfer USDC from escrow_usdc back to renter_usdc
-        token::transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                token::Transfer {
-                    from: ctx.accounts.escrow_usdc.to_account_info(),
-                    to: ctx.accounts.renter_usdc.to_account_info(),
-                    authority: rental_transaction_account_info.clone(), // Use pre-read value
-                },
-                signer_seeds,
-            ),
-            total_price_val, // Use pre-read value
+    // Rejects a pending request, refunding the escrow (price and deposit) to the renter
+    pub fn reject_rental_request(ctx: Context<RejectRentalRequest>) -> Result<()> {
+        require!(ctx.accounts.rental_transaction.status == RentalStatus::Requested, ErrorCode::InvalidRentalState);
+        refund_escrow(
+            &ctx.accounts.rental_transaction,
+            ctx.bumps.rental_transaction,
+            &ctx.accounts.escrow_usdc,
+            &ctx.accounts.renter_usdc,
+            &ctx.accounts.renter,
+            &ctx.accounts.token_program,
         )?;
+        ctx.accounts.rental_transaction.status = RentalStatus::Rejected;
 
-        // Close the escrow USDC account, funds go to renter
-        token::close_account(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                token::CloseAccount {
-                    account: ctx.accounts.escrow_usdc.to_account_info(),
-                    destination: ctx.accounts.renter.to_account_info(), // Rent goes back to renter
-                    authority: rental_transaction_account_info.clone(), // Use pre-read value
-                },
-                signer_seeds,
-            )
+        let rental_key = ctx.accounts.rental_transaction.key();
+        ctx.accounts.booking_schedule.release(&rental_key);
+
+        // The booking did not go ahead, so the promo can be used again
+        if let Some(promo) = ctx.accounts.promo.as_mut() {
+            promo.restore_use();
+        }
+        Ok(())
+    }
+
+    // Lets the renter withdraw a pending request before the owner answers it, refunding the escrow
+    // (price and deposit) in full
+    pub fn withdraw_rental_request(ctx: Context<WithdrawRentalRequest>) -> Result<()> {
+        require!(ctx.accounts.rental_transaction.status == RentalStatus::Requested, ErrorCode::InvalidRentalState);
+        refund_escrow(
+            &ctx.accounts.rental_transaction,
+            ctx.bumps.rental_transaction,
+            &ctx.accounts.escrow_usdc,
+            &ctx.accounts.renter_usdc,
+            &ctx.accounts.renter,
+            &ctx.accounts.token_program,
         )?;
+        ctx.accounts.rental_transaction.status = RentalStatus::Cancelled;
 
-        rental.is_active = false;
+        let rental_key = ctx.accounts.rental_transaction.key();
+        ctx.accounts.booking_schedule.release(&rental_key);
 
-        // CPI to item_registration to set item as available
-        let cpi_program = ctx.accounts.item_registration_program.to_account_info();
-        let cpi_accounts = ItemRegSetAvailabilityAccounts {
+        // The booking did not go ahead, so the promo can be used again
+        if let Some(promo) = ctx.accounts.promo.as_mut() {
+            promo.restore_use();
+        }
+        Ok(())
+    }
+
+    // Refunds a request the owner did not answer by its deadline and closes the request accounts,
+    // returning their rent to the renter. Anyone may call this once the deadline has passed.
+    pub fn expire_rental_request(ctx: Context<ExpireRentalRequest>) -> Result<()> {
+        let rental = &ctx.accounts.rental_transaction;
+        require!(rental.status == RentalStatus::Requested, ErrorCode::InvalidRentalState);
+        require!(Clock::get()?.unix_timestamp > rental.respond_by, ErrorCode::RentalRequestNotExpired);
+
+        refund_escrow(
+            &ctx.accounts.rental_transaction,
+            ctx.bumps.rental_transaction,
+            &ctx.accounts.escrow_usdc,
+            &ctx.accounts.renter_usdc,
+            &ctx.accounts.renter,
+            &ctx.accounts.token_program,
+        )?;
+
+        ctx.accounts.rental_transaction.status = RentalStatus::Expired;
+        emit_settlement(&ctx.accounts.rental_transaction);
+
+        let rental_key = ctx.accounts.rental_transaction.key();
+        ctx.accounts.booking_schedule.release(&rental_key);
+
+        // The booking did not go ahead, so the promo can be used again
+        if let Some(promo) = ctx.accounts.promo.as_mut() {
+            promo.restore_use();
+        }
+        Ok(())
+    }
+
+    // Closes a settled (completed, cancelled or rejected) rental, returning its rent to the renter.
+    // The final state is emitted as a RentalSettled event first, so indexers keep the history.
+    // Completed rentals can no longer be rated once closed.
+    pub fn close_rental(ctx: Context<CloseRental>) -> Result<()> {
+        let rental = &ctx.accounts.rental_transaction;
+        require!(
+            matches!(
+                rental.status,
+                RentalStatus::Completed | RentalStatus::Cancelled | RentalStatus::Rejected | RentalStatus::Resolved
+            ),
+            ErrorCode::RentalNotSettled
+        );
+        emit_settlement(rental);
+        Ok(())
+    }
+
+    // Lets the renter record when they returned the item, once the rental period is over. Late
+    // fees are charged at most up to this time; owners can dispute a false return.
+    pub fn record_return(ctx: Context<RecordReturn>) -> Result<()> {
+        let rental = &mut ctx.accounts.rental_transaction;
+        require!(rental.status == RentalStatus::Active, ErrorCode::InvalidRentalState);
+        require!(rental.returned_at == 0, ErrorCode::ReturnAlreadyRecorded);
+        let now = Clock::get()?.unix_timestamp;
+        require!(now >= rental.end_time, ErrorCode::RentalNotYetConcluded);
+        rental.returned_at = now;
+        Ok(())
+    }
+
+    // Lets the owner or renter of an active rental dispute it. The escrow is frozen (the rental can
+    // no longer be completed or cancelled) until the marketplace's arbiter rules on it.
+    pub fn open_dispute(ctx: Context<OpenDispute>) -> Result<()> {
+        let party = ctx.accounts.party.key();
+        let rental = &mut ctx.accounts.rental_transaction;
+        require!(party == rental.owner || party == rental.renter, ErrorCode::Unauthorized);
+        require!(rental.status == RentalStatus::Active, ErrorCode::InvalidRentalState);
+        rental.status = RentalStatus::Disputed;
+
+        let dispute = &mut ctx.accounts.dispute;
+        dispute.rental = rental.key();
+        dispute.opened_by = party;
+        dispute.opened_at = Clock::get()?.unix_timestamp;
+        dispute.evidence = Vec::new();
+        dispute.ruling = None;
+        dispute.bump = ctx.bumps.dispute;
+        Ok(())
+    }
+
+    // Adds an evidence URI (with a hash of its contents) to an open dispute; either party may submit
+    pub fn submit_dispute_evidence(ctx: Context<SubmitDisputeEvidence>, uri: String, content_hash: [u8; 32]) -> Result<()> {
+        require!(!uri.is_empty() && uri.len() <= MAX_REPORT_URI_LENGTH, ErrorCode::InvalidReportUri);
+        let rental = &ctx.accounts.rental_transaction;
+        let party = ctx.accounts.party.key();
+        require!(party == rental.owner || party == rental.renter, ErrorCode::Unauthorized);
+        require!(rental.status == RentalStatus::Disputed, ErrorCode::InvalidRentalState);
+
+        let dispute = &mut ctx.accounts.dispute;
+        require!(dispute.evidence.len() < MAX_DISPUTE_EVIDENCE, ErrorCode::DisputeEvidenceFull);
+        dispute.evidence.push(DisputeEvidence {
+            submitted_by: party,
+            uri,
+            content_hash,
+            submitted_at: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Settles a disputed rental. The arbiter refunds `renter_price_bps` of the price and
+    // `renter_deposit_bps` of the security deposit to the renter; the owner receives the rest, net
+    // of the platform fee on the part of the price they keep.
+    pub fn resolve_dispute(ctx: Context<ResolveDispute>, renter_price_bps: u16, renter_deposit_bps: u16) -> Result<()> {
+        let expected_system_pubkey = Pubkey::from_str(SYSTEM_REVENUE_ADDRESS).map_err(|_| ErrorCode::InvalidSystemAccount)?;
+        require_keys_eq!(ctx.accounts.system_usdc.owner, expected_system_pubkey, ErrorCode::InvalidSystemAccount);
+
+        let rental = &ctx.accounts.rental_transaction;
+        require!(rental.status == RentalStatus::Disputed, ErrorCode::InvalidRentalState);
+        let settlement = pricing::split_escrow(
+            rental.total_price,
+            rental.platform_fee,
+            rental.security_deposit,
+            renter_price_bps,
+            renter_deposit_bps,
+        ).map_err(pricing_error)?;
+
+        // Pay out the escrow; its rent goes back to the renter, who paid for it
+        settle_escrow(
+            rental,
+            ctx.bumps.rental_transaction,
+            &ctx.accounts.escrow_usdc,
+            &[
+                (&ctx.accounts.renter_usdc, settlement.renter_amount),
+                (&ctx.accounts.owner_usdc, settlement.owner_amount),
+                (&ctx.accounts.system_usdc, settlement.platform_fee),
+            ],
+            &ctx.accounts.renter,
+            &ctx.accounts.token_program,
+        )?;
+
+        let payment_mint = rental.payment_mint;
+        let started = rental.started;
+        ctx.accounts.rental_transaction.status = RentalStatus::Resolved;
+        ctx.accounts.dispute.ruling = Some(DisputeRuling {
+            arbiter: ctx.accounts.arbiter.key(),
+            renter_price_bps,
+            renter_deposit_bps,
+            resolved_at: Clock::get()?.unix_timestamp,
+        });
+
+        // Free the rental window in the item's schedule
+        let rental_key = ctx.accounts.rental_transaction.key();
+        ctx.accounts.booking_schedule.release(&rental_key);
+
+        // Update marketplace statistics
+        record_rental_activity(
+            &ctx.accounts.item_registration_program,
+            &ctx.accounts.item_account,
+            &ctx.accounts.item_registry,
+            &ctx.accounts.rental_authority,
+            ctx.bumps.rental_authority,
+            settlement_activity(payment_mint, &settlement, started),
+        )
+    }
+
+    // Lets the renter score a completed rental once; the score is aggregated on the item
+    pub fn rate_rental(ctx: Context<RateRental>, score: u8) -> Result<()> {
+        let rental = &mut ctx.accounts.rental_transaction;
+        require!(rental.status == RentalStatus::Completed, ErrorCode::RentalNotCompleted);
+        require!(!rental.is_rated, ErrorCode::RentalAlreadyRated);
+        rental.is_rated = true;
+
+        // CPI to item_registration to update the item's rating aggregate
+        let bump = [ctx.bumps.rental_authority];
+        let seeds: &[&[u8]] = &[RENTAL_AUTHORITY_SEED, &bump];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = ItemRegRecordRatingAccounts {
             item_account: ctx.accounts.item_account.to_account_info(),
-            owner: ctx.accounts.owner.to_account_info(),
+            rental_authority: ctx.accounts.rental_authority.to_account_info(),
         };
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        item_reg_set_availability(cpi_ctx, true)?; // true for available
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.item_registration_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        item_reg_record_rating(cpi_ctx, score)?;
+
+        Ok(())
+    }
 
+    // Creates the condition report for a rental; either party may open it
+    pub fn open_condition_report(ctx: Context<OpenConditionReport>) -> Result<()> {
+        let rental = &ctx.accounts.rental_transaction;
+        let reporter = ctx.accounts.reporter.key();
+        require!(reporter == rental.owner || reporter == rental.renter, ErrorCode::UnauthorizedReporter);
+
+        let report = &mut ctx.accounts.condition_report;
+        report.rental = rental.key();
+        report.owner_handover = None;
+        report.renter_handover = None;
+        report.owner_return = None;
+        report.renter_return = None;
+        report.bump = ctx.bumps.condition_report;
+        Ok(())
+    }
+
+    // Records the caller's photo bundle for a stage of the rental. Each entry can be written once.
+    pub fn record_condition(
+        ctx: Context<RecordCondition>,
+        stage: ReportStage,
+        uri: String,
+        content_hash: [u8; 32],
+    ) -> Result<()> {
+        require!(!uri.is_empty() && uri.len() <= MAX_REPORT_URI_LENGTH, ErrorCode::InvalidReportUri);
+
+        let rental = &ctx.accounts.rental_transaction;
+        match stage {
+            // Handover evidence is recorded while the rental is running
+            ReportStage::Handover => require!(rental.status == RentalStatus::Active, ErrorCode::InvalidRentalState),
+            // Return evidence may also be recorded right after the owner completes the rental, or
+            // while a dispute over the return is open, when it matters most
+            ReportStage::Return => require!(
+                matches!(rental.status, RentalStatus::Active | RentalStatus::Completed | RentalStatus::Disputed),
+                ErrorCode::InvalidRentalState
+            ),
+        }
+
+        let reporter = ctx.accounts.reporter.key();
+        let report = &mut ctx.accounts.condition_report;
+        let entry = if reporter == rental.owner {
+            match stage {
+                ReportStage::Handover => &mut report.owner_handover,
+                ReportStage::Return => &mut report.owner_return,
+            }
+        } else if reporter == rental.renter {
+            match stage {
+                ReportStage::Handover => &mut report.renter_handover,
+                ReportStage::Return => &mut report.renter_return,
+            }
+        } else {
+            return err!(ErrorCode::UnauthorizedReporter);
+        };
+        require!(entry.is_none(), ErrorCode::ConditionAlreadyRecorded);
+
+        *entry = Some(ConditionEntry {
+            uri,
+            content_hash,
+            recorded_at: Clock::get()?.unix_timestamp,
+        });
         Ok(())
     }
 }
@@ -225,26 +867,420 @@ pub struct RentalTransaction {
     pub start_time: i64,
     pub end_time: i64,
     pub total_price: u64,
-    pub is_active: bool,
-    pub is_completed: bool,
+    pub status: RentalStatus,
+    pub is_rated: bool, // Set once the renter has scored the completed rental
+    pub discount: u64,     // Promo discount applied at booking; total_price is after the discount
+    pub platform_fee: u64, // Fee owed to the platform out of total_price on completion
+    pub promo: Option<Pubkey>, // Promo redeemed at booking, if any
+    pub security_deposit: u64, // Escrowed next to total_price and returned to the renter when settled
+    pub payment_mint: Pubkey,  // Mint held in escrow; all amounts above are in its base units
+    pub respond_by: i64,       // Deadline for the owner to answer a request; 0 for instant bookings
+    pub nonce: u64,            // The item's rental nonce at booking, part of the PDA seeds
+    pub cancellation_policy: CancellationPolicy, // The item's policy at booking
+    pub late_fee_per_hour: u64,  // The item's late fee at booking, in base units of payment_mint
+    pub late_grace_minutes: u16,
+    pub returned_at: i64,        // Return time recorded by the renter; 0 if not recorded
+    pub late_fee: u64,           // Charged from the security deposit on completion
+    pub started: bool,           // Set by start_rental once the rental period has begun
 }
 
 impl RentalTransaction {
-    // 8 (discriminator) + 3*32 (Pubkeys) + 3*8 (u64/i64) + 2*1 (bools)
-    pub const LEN: usize = 8 + (3 * 32) + (3 * 8) + (2 * 1);
+    // 8 (discriminator) + 4*32 (Pubkeys) + 8*8 (u64/i64) + 1 (status) + 1 (is_rated) + 1 + 32 (promo)
+    // + 1 (cancellation_policy) + 3*8 (late_fee_per_hour, returned_at, late_fee) + 2 (late_grace_minutes)
+    // + 1 (started)
+    pub const LEN: usize = 8 + (4 * 32) + (8 * 8) + 1 + 1 + (1 + 32) + 1 + (3 * 8) + 2 + 1;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RentalStatus {
+    Requested, // Funds escrowed, waiting for the owner of a request-to-book item
+    Active,
+    Completed,
+    Cancelled,
+    Rejected, // Request declined by the owner; the escrow was refunded
+    Expired,  // Request not answered in time; the escrow was refunded
+    Disputed, // Escrow frozen until the arbiter rules
+    Resolved, // Escrow split by the arbiter's ruling
+}
+
+// Final state of a rental, emitted when its account is closed
+#[event]
+pub struct RentalSettled {
+    pub rental: Pubkey,
+    pub item: Pubkey,
+    pub renter: Pubkey,
+    pub owner: Pubkey,
+    pub nonce: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub status: RentalStatus,
+    pub payment_mint: Pubkey,
+    pub total_price: u64,
+    pub discount: u64,
+    pub platform_fee: u64,
+    pub security_deposit: u64,
+    pub late_fee: u64,
+    pub promo: Option<Pubkey>,
+    pub is_rated: bool,
+}
+
+// Reserved rental windows of an item. Bookings are checked against it instead of the item's
+// is_available flag, so an item can be booked for several non-overlapping periods.
+#[account]
+pub struct BookingSchedule {
+    pub item: Pubkey,
+    pub bookings: Vec<ScheduledBooking>, // Max MAX_SCHEDULED_BOOKINGS
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct ScheduledBooking {
+    pub rental: Pubkey,  // RentalTransaction holding the reservation
+    pub start_time: i64, // Reserved window, [start_time, end_time)
+    pub end_time: i64,
+}
+
+impl BookingSchedule {
+    // 8 (discriminator) + 32 (item) + 4 + MAX_SCHEDULED_BOOKINGS*(32 + 2*8) (bookings) + 1 (bump)
+    pub const LEN: usize = 8 + 32 + 4 + MAX_SCHEDULED_BOOKINGS * (32 + 2 * 8) + 1;
+
+    // Records the item on a newly created schedule; no-op once the schedule is open
+    fn open(&mut self, item: Pubkey, bump: u8) {
+        if self.item == Pubkey::default() {
+            self.item = item;
+            self.bookings = Vec::new();
+            self.bump = bump;
+        }
+    }
+
+    // Fails if [start_time, end_time) overlaps a reserved window
+    fn check_available(&self, start_time: i64, end_time: i64) -> Result<()> {
+        let overlaps = self.bookings.iter().any(|booking| start_time < booking.end_time && booking.start_time < end_time);
+        require!(!overlaps, ErrorCode::BookingConflict);
+        Ok(())
+    }
+
+    fn reserve(&mut self, rental: Pubkey, start_time: i64, end_time: i64) -> Result<()> {
+        self.check_available(start_time, end_time)?;
+        require!(self.bookings.len() < MAX_SCHEDULED_BOOKINGS, ErrorCode::BookingScheduleFull);
+        self.bookings.push(ScheduledBooking { rental, start_time, end_time });
+        Ok(())
+    }
+
+    // Removes the rental's window, if reserved
+    fn release(&mut self, rental: &Pubkey) {
+        self.bookings.retain(|booking| booking.rental != *rental);
+    }
+}
+
+#[account]
+pub struct Hold {
+    pub holder: Pubkey,
+    pub item: Pubkey,
+    pub start_time: i64, // Held slot, [start_time, end_time)
+    pub end_time: i64,
+    pub expires_at: i64, // The hold is ignored from this time on
+    pub bond: u64,       // Lamports held as a refundable bond
+    pub bump: u8,
+}
+
+impl Hold {
+    // 8 (discriminator) + 2*32 (Pubkeys) + 3*8 (i64) + 8 (bond) + 1 (bump)
+    pub const LEN: usize = 8 + (2 * 32) + (3 * 8) + 8 + 1;
+}
+
+// Per-marketplace settings, one per item registry
+#[account]
+pub struct PlatformConfig {
+    pub registry: Pubkey, // Item registry of the marketplace this config belongs to
+    pub admin: Pubkey,
+    pub bump: u8,
+    pub sol_usd_feed: Pubkey, // SOL/USD price account for SOL payments; default pubkey disables them
+    pub usd_mint: Pubkey,     // USD mint items must be priced in to accept SOL payments
+    pub arbiter: Pubkey,      // Rules on disputes; default pubkey disables disputes
+}
+
+impl PlatformConfig {
+    // 8 (discriminator) + 2*32 (registry, admin) + 1 (bump) + 2*32 (sol_usd_feed, usd_mint) + 32 (arbiter)
+    pub const LEN: usize = 8 + (2 * 32) + 1 + (2 * 32) + 32;
+}
+
+#[account]
+pub struct Promo {
+    pub registry: Pubkey, // Marketplace the promo is valid in
+    pub creator: Pubkey,
+    pub code: String, // Max 32 characters (enforced by MAX_PROMO_CODE_LENGTH)
+    pub discount: Discount,
+    pub expires_at: i64,
+    pub max_uses: u32,
+    pub uses: u32,
+    pub item: Option<Pubkey>,  // Only redeemable for this item, if set
+    pub owner: Option<Pubkey>, // Only redeemable for this owner's items, if set
+    pub platform_funded: bool, // Created by the platform admin; the discount comes out of the platform fee
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Discount {
+    Percentage { bps: u16 }, // Basis points off the rental price
+    Fixed { amount: u64 },   // Base units off the rental price
+}
+
+impl Promo {
+    // 8 (discriminator) + 2*32 (registry, creator) + 4 + 32 (code) + 1 + 8 (discount) + 8 (expires_at)
+    // + 2*4 (max_uses, uses) + 2*(1 + 32) (item, owner) + 1 (platform_funded) + 1 (bump)
+    pub const LEN: usize = 8 + (2 * 32) + 4 + MAX_PROMO_CODE_LENGTH + (1 + 8) + 8 + (2 * 4) + (2 * (1 + 32)) + 1 + 1;
+
+    // Checks the promo applies to `item` and records one use
+    fn redeem(&mut self, item: &Account<ItemAccount>, now: i64) -> Result<Promotion> {
+        let promotion = self.promotion(item, now)?;
+        self.uses += 1;
+        Ok(promotion)
+    }
+
+    // Gives back a use recorded by redeem
+    fn restore_use(&mut self) {
+        self.uses = self.uses.saturating_sub(1);
+    }
+
+    // Checks the promo applies to `item` without using it
+    fn promotion(&self, item: &Account<ItemAccount>, now: i64) -> Result<Promotion> {
+        require!(now < self.expires_at, ErrorCode::PromoExpired);
+        require!(self.uses < self.max_uses, ErrorCode::PromoExhausted);
+        require_keys_eq!(self.registry, item.registry, ErrorCode::PromoNotApplicable);
+        if let Some(scope) = self.item {
+            require_keys_eq!(scope, item.key(), ErrorCode::PromoNotApplicable);
+        }
+        if let Some(scope) = self.owner {
+            require_keys_eq!(scope, item.owner, ErrorCode::PromoNotApplicable);
+        }
+
+        let discount = match self.discount {
+            Discount::Percentage { bps } => PromoDiscount::Percentage { bps },
+            Discount::Fixed { amount } => PromoDiscount::Fixed { amount },
+        };
+        Ok(Promotion { discount, platform_funded: self.platform_funded })
+    }
+}
+
+// Price breakdown returned by quote_rental
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct RentalQuote {
+    pub start_time: i64,
+    pub end_time: i64,
+    pub base_price: u64,
+    pub rule_adjustment: i64,
+    pub list_price: u64,
+    pub discount: u64,
+    pub total_price: u64,
+    pub platform_fee: u64,
+    pub owner_payout: u64,
+    pub security_deposit: u64,
+    pub amount_due: u64, // Transferred from the renter into escrow by initiate_rental
+}
+
+impl RentalQuote {
+    fn new(start_time: i64, end_time: i64, price: PriceBreakdown) -> Self {
+        RentalQuote {
+            start_time,
+            end_time,
+            base_price: price.base_price,
+            rule_adjustment: price.rule_adjustment,
+            list_price: price.list_price,
+            discount: price.discount,
+            total_price: price.total_price,
+            platform_fee: price.platform_fee,
+            owner_payout: price.owner_payout,
+            security_deposit: price.security_deposit,
+            amount_due: price.amount_due,
+        }
+    }
+}
+
+// Handover and return evidence recorded by each party of a rental
+#[account]
+pub struct ConditionReport {
+    pub rental: Pubkey,
+    pub owner_handover: Option<ConditionEntry>,
+    pub renter_handover: Option<ConditionEntry>,
+    pub owner_return: Option<ConditionEntry>,
+    pub renter_return: Option<ConditionEntry>,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ConditionEntry {
+    pub uri: String,           // Photo-bundle URI, max 200 characters
+    pub content_hash: [u8; 32], // Hash of the bundle contents, so the evidence cannot be swapped later
+    pub recorded_at: i64,
+}
+
+impl ConditionEntry {
+    // 4 + 200 (uri) + 32 (content_hash) + 8 (recorded_at)
+    pub const LEN: usize = 4 + MAX_REPORT_URI_LENGTH + 32 + 8;
+}
+
+impl ConditionReport {
+    // 8 (discriminator) + 32 (rental) + 4*(1 + ConditionEntry::LEN) (entries) + 1 (bump)
+    pub const LEN: usize = 8 + 32 + 4 * (1 + ConditionEntry::LEN) + 1;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ReportStage {
+    Handover,
+    Return,
+}
+
+// A disputed rental: evidence submitted by both parties and, once settled, the arbiter's ruling
+#[account]
+pub struct Dispute {
+    pub rental: Pubkey,
+    pub opened_by: Pubkey, // Owner or renter
+    pub opened_at: i64,
+    pub evidence: Vec<DisputeEvidence>, // Max MAX_DISPUTE_EVIDENCE
+    pub ruling: Option<DisputeRuling>,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct DisputeEvidence {
+    pub submitted_by: Pubkey,
+    pub uri: String,            // Max 200 characters
+    pub content_hash: [u8; 32], // Hash of the evidence contents, so it cannot be swapped later
+    pub submitted_at: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct DisputeRuling {
+    pub arbiter: Pubkey,
+    pub renter_price_bps: u16,   // Share of the price refunded to the renter
+    pub renter_deposit_bps: u16, // Share of the security deposit returned to the renter
+    pub resolved_at: i64,
+}
+
+impl DisputeEvidence {
+    // 32 (submitted_by) + 4 + 200 (uri) + 32 (content_hash) + 8 (submitted_at)
+    pub const LEN: usize = 32 + 4 + MAX_REPORT_URI_LENGTH + 32 + 8;
+}
+
+impl Dispute {
+    // 8 (discriminator) + 2*32 (rental, opened_by) + 8 (opened_at)
+    // + 4 + MAX_DISPUTE_EVIDENCE*DisputeEvidence::LEN (evidence) + 1 + 32 + 2*2 + 8 (ruling) + 1 (bump)
+    pub const LEN: usize = 8 + (2 * 32) + 8 + 4 + MAX_DISPUTE_EVIDENCE * DisputeEvidence::LEN + (1 + 32 + 2 * 2 + 8) + 1;
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>, // Authority of the marketplace's item registry
+    #[account(has_one = authority @ ErrorCode::Unauthorized)]
+    pub item_registry: Account<'info, ItemRegistry>,
+    #[account(init, payer = authority, space = PlatformConfig::LEN, seeds = [b"platform_config".as_ref(), item_registry.key().as_ref()], bump)]
+    pub platform_config: Account<'info, PlatformConfig>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, seeds = [b"platform_config".as_ref(), platform_config.registry.as_ref()], bump = platform_config.bump, has_one = admin @ ErrorCode::Unauthorized)]
+    pub platform_config: Account<'info, PlatformConfig>,
+}
+
+#[derive(Accounts)]
+#[instruction(code: String)]
+pub struct CreatePromo<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>, // Platform admin or an item owner
+    #[account(seeds = [b"platform_config".as_ref(), platform_config.registry.as_ref()], bump = platform_config.bump)]
+    pub platform_config: Account<'info, PlatformConfig>,
+    // Codes are unique per creator within a marketplace, so no one can claim another creator's code
+    #[account(init, payer = creator, space = Promo::LEN, seeds = [b"promo".as_ref(), platform_config.registry.as_ref(), creator.key().as_ref(), code.as_bytes()], bump)]
+    pub promo: Account<'info, Promo>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClosePromo<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    #[account(mut, has_one = creator @ ErrorCode::Unauthorized, close = creator)]
+    pub promo: Account<'info, Promo>,
+}
+
+#[derive(Accounts)]
+pub struct OpenBookingSchedule<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub item_account: Account<'info, ItemAccount>,
+    #[account(init, payer = payer, space = BookingSchedule::LEN, seeds = [b"booking_schedule".as_ref(), item_account.key().as_ref()], bump)]
+    pub booking_schedule: Account<'info, BookingSchedule>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PlaceHold<'info> {
+    #[account(mut)]
+    pub holder: Signer<'info>,
+    pub item_account: Account<'info, ItemAccount>,
+    /// CHECK: The item's hold PDA; created in place_hold, replacing an expired hold if there is one
+    #[account(mut, seeds = [b"hold".as_ref(), item_account.key().as_ref()], bump)]
+    pub hold: UncheckedAccount<'info>,
+    /// CHECK: Holder of the expired hold being replaced, if any; checked against the hold in place_hold
+    #[account(mut)]
+    pub previous_holder: Option<UncheckedAccount<'info>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseHold<'info> {
+    pub caller: Signer<'info>, // The holder, or anyone once the hold has expired
+    /// CHECK: Receives the bond and rent; must be the holder recorded on the hold
+    #[account(mut, address = hold.holder)]
+    pub holder: UncheckedAccount<'info>,
+    #[account(mut, close = holder, seeds = [b"hold".as_ref(), hold.item.as_ref()], bump = hold.bump)]
+    pub hold: Account<'info, Hold>,
+}
+
+#[derive(Accounts)]
+pub struct QuoteRental<'info> {
+    /// CHECK: Wallet the quote is for; only its key is used
+    pub renter: UncheckedAccount<'info>,
+    pub item_account: Account<'info, ItemAccount>,
+    // Config of the marketplace the item is listed in
+    #[account(seeds = [b"platform_config".as_ref(), item_account.registry.as_ref()], bump = platform_config.bump)]
+    pub platform_config: Account<'info, PlatformConfig>,
+    #[account(seeds = [ATTESTATION_SEED, renter.key().as_ref()], bump, seeds::program = item_registration_program.key())]
+    pub attestation: Option<Account<'info, Attestation>>,
+    pub promo: Option<Account<'info, Promo>>, // Promo code to apply, if any; not redeemed
+    // The item's reserved rental windows
+    #[account(seeds = [b"booking_schedule".as_ref(), item_account.key().as_ref()], bump = booking_schedule.bump)]
+    pub booking_schedule: Account<'info, BookingSchedule>,
+    /// CHECK: The item's hold PDA, which may not exist; checked in check_hold
+    #[account(seeds = [b"hold".as_ref(), item_account.key().as_ref()], bump)]
+    pub hold: UncheckedAccount<'info>,
+    pub item_registration_program: Program<'info, ItemRegistrationProgram>,
 }
 
 #[derive(Accounts)]
 pub struct InitiateRental<'info> {
     #[account(mut)]
     pub renter: Signer<'info>,
-    // owner is the owner of the item_account and needs to sign for CPI to item_registration
-    #[account(mut)]
-    pub owner: Signer<'info>,
+    /// CHECK: Owner of the item_account, recorded on the rental; does not sign
+    #[account(address = item_account.owner)]
+    pub owner: UncheckedAccount<'info>,
     #[account(mut)]
     pub item_account: Account<'info, ItemAccount>,
-    #[account(init, payer = renter, space = RentalTransaction::LEN, seeds = [b"rental_transaction".as_ref(), item_account.key().as_ref(), renter.key().as_ref()], bump)]
+    #[account(init, payer = renter, space = RentalTransaction::LEN, seeds = [b"rental_transaction".as_ref(), item_account.key().as_ref(), renter.key().as_ref(), item_account.next_rental_nonce.to_le_bytes().as_ref()], bump)]
     pub rental_transaction: Account<'info, RentalTransaction>,
+    // The item's reserved rental windows, created on the item's first booking if needed
+    #[account(init_if_needed, payer = renter, space = BookingSchedule::LEN, seeds = [b"booking_schedule".as_ref(), item_account.key().as_ref()], bump)]
+    pub booking_schedule: Account<'info, BookingSchedule>,
+    // Payment mint, must be the mint the item is priced in
+    #[account(
+        address = item_account.price_mint @ ErrorCode::PaymentMintMismatch,
+        constraint = usdc_mint.decimals == item_account.price_decimals @ ErrorCode::PaymentMintMismatch
+    )]
     pub usdc_mint: Account<'info, Mint>,
     #[account(mut, token::mint = usdc_mint)] // Renter's USDC account, tokens are transferred from here
     pub renter_usdc: Account<'info, TokenAccount>,
@@ -253,6 +1289,75 @@ pub struct InitiateRental<'info> {
     // pub owner_usdc: Account<'info, TokenAccount>,
     #[account(init, payer = renter, associated_token::mint = usdc_mint, associated_token::authority = rental_transaction)] // Escrow's USDC token account, initialized as ATA
     pub escrow_usdc: Account<'info, TokenAccount>,
+    #[account(mut)] // Registry the item is listed in, for marketplace statistics
+    pub item_registry: Account<'info, ItemRegistry>,
+    // Config of the marketplace; the item must be listed in the same marketplace
+    #[account(
+        seeds = [b"platform_config".as_ref(), item_registry.key().as_ref()],
+        bump = platform_config.bump,
+        constraint = platform_config.registry == item_account.registry @ ErrorCode::MarketplaceMismatch
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+    /// CHECK: PDA that signs CPIs into item_registration; holds no data
+    #[account(seeds = [RENTAL_AUTHORITY_SEED], bump)]
+    pub rental_authority: UncheckedAccount<'info>,
+    // Renter's identity attestation, required for items flagged as requiring verified renters
+    #[account(seeds = [ATTESTATION_SEED, renter.key().as_ref()], bump, seeds::program = item_registration_program.key())]
+    pub attestation: Option<Account<'info, Attestation>>,
+    #[account(mut)] // Promo code to redeem, if any
+    pub promo: Option<Account<'info, Promo>>,
+    /// CHECK: The item's hold PDA, which may not exist; checked and consumed in honor_hold
+    #[account(mut, seeds = [b"hold".as_ref(), item_account.key().as_ref()], bump)]
+    pub hold: UncheckedAccount<'info>,
+    pub item_registration_program: Program<'info, ItemRegistrationProgram>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct InitiateRentalWithSol<'info> {
+    #[account(mut)]
+    pub renter: Signer<'info>,
+    /// CHECK: Owner of the item_account, recorded on the rental; does not sign
+    #[account(address = item_account.owner)]
+    pub owner: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub item_account: Account<'info, ItemAccount>,
+    #[account(init, payer = renter, space = RentalTransaction::LEN, seeds = [b"rental_transaction".as_ref(), item_account.key().as_ref(), renter.key().as_ref(), item_account.next_rental_nonce.to_le_bytes().as_ref()], bump)]
+    pub rental_transaction: Account<'info, RentalTransaction>,
+    // The item's reserved rental windows, created on the item's first booking if needed
+    #[account(init_if_needed, payer = renter, space = BookingSchedule::LEN, seeds = [b"booking_schedule".as_ref(), item_account.key().as_ref()], bump)]
+    pub booking_schedule: Account<'info, BookingSchedule>,
+    #[account(address = spl_token::native_mint::ID)]
+    pub native_mint: Account<'info, Mint>,
+    #[account(init, payer = renter, associated_token::mint = native_mint, associated_token::authority = rental_transaction)] // Wrapped SOL escrow, initialized as ATA
+    pub escrow_wsol: Account<'info, TokenAccount>,
+    #[account(mut)] // Registry the item is listed in, for marketplace statistics
+    pub item_registry: Account<'info, ItemRegistry>,
+    // Config of the marketplace; the item must be listed in the same marketplace and priced in USD
+    #[account(
+        seeds = [b"platform_config".as_ref(), item_registry.key().as_ref()],
+        bump = platform_config.bump,
+        constraint = platform_config.registry == item_account.registry @ ErrorCode::MarketplaceMismatch,
+        constraint = platform_config.sol_usd_feed != Pubkey::default() @ ErrorCode::SolPaymentsDisabled,
+        constraint = platform_config.usd_mint == item_account.price_mint @ ErrorCode::ItemNotUsdPriced
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+    /// CHECK: SOL/USD price account configured for the marketplace; parsed in read_sol_usd_price
+    #[account(address = platform_config.sol_usd_feed @ ErrorCode::InvalidPriceFeed)]
+    pub price_feed: UncheckedAccount<'info>,
+    /// CHECK: PDA that signs CPIs into item_registration; holds no data
+    #[account(seeds = [RENTAL_AUTHORITY_SEED], bump)]
+    pub rental_authority: UncheckedAccount<'info>,
+    // Renter's identity attestation, required for items flagged as requiring verified renters
+    #[account(seeds = [ATTESTATION_SEED, renter.key().as_ref()], bump, seeds::program = item_registration_program.key())]
+    pub attestation: Option<Account<'info, Attestation>>,
+    #[account(mut)] // Promo code to redeem, if any
+    pub promo: Option<Account<'info, Promo>>,
+    /// CHECK: The item's hold PDA, which may not exist; checked and consumed in honor_hold
+    #[account(mut, seeds = [b"hold".as_ref(), item_account.key().as_ref()], bump)]
+    pub hold: UncheckedAccount<'info>,
     pub item_registration_program: Program<'info, ItemRegistrationProgram>,
     pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
@@ -263,17 +1368,34 @@ pub struct InitiateRental<'info> {
 pub struct CompleteRental<'info> {
     #[account(mut)]
     pub owner: Signer<'info>,
-    #[account(mut, seeds = [b"rental_transaction".as_ref(), item_account.key().as_ref(), rental_transaction.renter.as_ref()], bump)]
+    #[account(mut, has_one = owner @ ErrorCode::Unauthorized, seeds = [b"rental_transaction".as_ref(), item_account.key().as_ref(), rental_transaction.renter.as_ref(), rental_transaction.nonce.to_le_bytes().as_ref()], bump)]
     pub rental_transaction: Account<'info, RentalTransaction>,
     #[account(mut)]
     pub item_account: Account<'info, ItemAccount>,
+    // The item's reserved rental windows
+    #[account(mut, seeds = [b"booking_schedule".as_ref(), item_account.key().as_ref()], bump = booking_schedule.bump)]
+    pub booking_schedule: Account<'info, BookingSchedule>,
     pub usdc_mint: Account<'info, Mint>,
     #[account(mut, token::mint = usdc_mint)] // Owner's USDC account, tokens are received here
     pub owner_usdc: Account<'info, TokenAccount>,
     #[account(mut, token::mint = usdc_mint)] // System's USDC account, receives platform fee
     pub system_usdc: Account<'info, TokenAccount>,
+    // Renter's USDC account, receives the security deposit back
+    #[account(mut, token::mint = usdc_mint, token::authority = rental_transaction.renter)]
+    pub renter_usdc: Account<'info, TokenAccount>,
     #[account(mut, close = owner, token::mint = usdc_mint)] // Escrow's USDC token account
     pub escrow_usdc: Account<'info, TokenAccount>,
+    #[account(mut)] // Registry the item is listed in, for marketplace statistics
+    pub item_registry: Account<'info, ItemRegistry>,
+    /// CHECK: PDA that signs CPIs into item_registration; holds no data
+    #[account(seeds = [RENTAL_AUTHORITY_SEED], bump)]
+    pub rental_authority: UncheckedAccount<'info>,
+    /// CHECK: The owner's profile PDA, which may not exist; its rental counter is incremented if it does
+    #[account(mut, seeds = [USER_PROFILE_SEED, rental_transaction.owner.as_ref()], bump, seeds::program = item_registration_program.key())]
+    pub owner_profile: UncheckedAccount<'info>,
+    /// CHECK: The renter's profile PDA, which may not exist; its rental counter is incremented if it does
+    #[account(mut, seeds = [USER_PROFILE_SEED, rental_transaction.renter.as_ref()], bump, seeds::program = item_registration_program.key())]
+    pub renter_profile: UncheckedAccount<'info>,
     pub item_registration_program: Program<'info, ItemRegistrationProgram>,
     pub token_program: Program<'info, Token>,
 }
@@ -282,18 +1404,238 @@ pub struct CompleteRental<'info> {
 pub struct CancelRental<'info> {
     #[account(mut)]
     pub renter: Signer<'info>,
-    // Owner of the item_account, must be a signer for CPI to mark item available
+    // Owner of the rental's item; co-signing waives the cancellation policy (full refund)
+    pub owner: Option<Signer<'info>>,
+    #[account(mut, seeds = [b"rental_transaction".as_ref(), item_account.key().as_ref(), renter.key().as_ref(), rental_transaction.nonce.to_le_bytes().as_ref()], bump)]
+    pub rental_transaction: Account<'info, RentalTransaction>,
     #[account(mut)]
+    pub item_account: Account<'info, ItemAccount>,
+    // The item's reserved rental windows
+    #[account(mut, seeds = [b"booking_schedule".as_ref(), item_account.key().as_ref()], bump = booking_schedule.bump)]
+    pub booking_schedule: Account<'info, BookingSchedule>,
+    // Promo redeemed at booking, if any; the use is given back
+    #[account(mut, constraint = rental_transaction.promo == Some(promo.key()) @ ErrorCode::PromoNotApplicable)]
+    pub promo: Option<Account<'info, Promo>>,
+    #[account(mut, associated_token::mint = rental_transaction.payment_mint, associated_token::authority = rental_transaction)]
+    pub escrow_usdc: Account<'info, TokenAccount>,
+    // Token accounts of the payment mint receiving the renter's, owner's and platform's shares
+    #[account(mut, token::mint = rental_transaction.payment_mint, token::authority = renter)]
+    pub renter_usdc: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = rental_transaction.payment_mint, token::authority = rental_transaction.owner)]
+    pub owner_usdc: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = rental_transaction.payment_mint)]
+    pub system_usdc: Account<'info, TokenAccount>,
+    #[account(mut)] // Registry the item is listed in, for marketplace statistics
+    pub item_registry: Account<'info, ItemRegistry>,
+    /// CHECK: PDA that signs CPIs into item_registration; holds no data
+    #[account(seeds = [RENTAL_AUTHORITY_SEED], bump)]
+    pub rental_authority: UncheckedAccount<'info>,
+    pub item_registration_program: Program<'info, ItemRegistrationProgram>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptRentalRequest<'info> {
     pub owner: Signer<'info>,
-    #[account(mut, seeds = [b"rental_transaction".as_ref(), item_account.key().as_ref(), renter.key().as_ref()], bump)]
+    #[account(mut, has_one = owner @ ErrorCode::Unauthorized, seeds = [b"rental_transaction".as_ref(), item_account.key().as_ref(), rental_transaction.renter.as_ref(), rental_transaction.nonce.to_le_bytes().as_ref()], bump)]
     pub rental_transaction: Account<'info, RentalTransaction>,
     #[account(mut)]
     pub item_account: Account<'info, ItemAccount>,
-    pub usdc_mint: Account<'info, Mint>,
-    #[account(mut, token::mint = usdc_mint)] // Renter's USDC account, tokens are returned here
+    #[account(mut)] // Registry the item is listed in, for marketplace statistics
+    pub item_registry: Account<'info, ItemRegistry>,
+    /// CHECK: PDA that signs CPIs into item_registration; holds no data
+    #[account(seeds = [RENTAL_AUTHORITY_SEED], bump)]
+    pub rental_authority: UncheckedAccount<'info>,
+    pub item_registration_program: Program<'info, ItemRegistrationProgram>,
+}
+
+#[derive(Accounts)]
+pub struct RejectRentalRequest<'info> {
+    pub owner: Signer<'info>,
+    /// CHECK: The renter recorded on the rental; receives the escrow account's rent
+    #[account(mut, address = rental_transaction.renter)]
+    pub renter: UncheckedAccount<'info>,
+    #[account(mut, has_one = owner @ ErrorCode::Unauthorized, seeds = [b"rental_transaction".as_ref(), rental_transaction.item.as_ref(), rental_transaction.renter.as_ref(), rental_transaction.nonce.to_le_bytes().as_ref()], bump)]
+    pub rental_transaction: Account<'info, RentalTransaction>,
+    // The item's reserved rental windows
+    #[account(mut, seeds = [b"booking_schedule".as_ref(), rental_transaction.item.as_ref()], bump = booking_schedule.bump)]
+    pub booking_schedule: Account<'info, BookingSchedule>,
+    // Promo redeemed at booking, if any; the use is given back
+    #[account(mut, constraint = rental_transaction.promo == Some(promo.key()) @ ErrorCode::PromoNotApplicable)]
+    pub promo: Option<Account<'info, Promo>>,
+    #[account(mut, associated_token::mint = rental_transaction.payment_mint, associated_token::authority = rental_transaction)]
+    pub escrow_usdc: Account<'info, TokenAccount>,
+    // Renter's token account of the payment mint, receives the refund
+    #[account(mut, token::mint = rental_transaction.payment_mint, token::authority = rental_transaction.renter)]
     pub renter_usdc: Account<'info, TokenAccount>,
-    #[account(mut, close = renter, token::mint = usdc_mint)] // Escrow's USDC token account
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawRentalRequest<'info> {
+    #[account(mut)]
+    pub renter: Signer<'info>, // Receives the escrow account's rent
+    #[account(mut, has_one = renter @ ErrorCode::Unauthorized, seeds = [b"rental_transaction".as_ref(), rental_transaction.item.as_ref(), renter.key().as_ref(), rental_transaction.nonce.to_le_bytes().as_ref()], bump)]
+    pub rental_transaction: Account<'info, RentalTransaction>,
+    // The item's reserved rental windows
+    #[account(mut, seeds = [b"booking_schedule".as_ref(), rental_transaction.item.as_ref()], bump = booking_schedule.bump)]
+    pub booking_schedule: Account<'info, BookingSchedule>,
+    // Promo redeemed at booking, if any; the use is given back
+    #[account(mut, constraint = rental_transaction.promo == Some(promo.key()) @ ErrorCode::PromoNotApplicable)]
+    pub promo: Option<Account<'info, Promo>>,
+    #[account(mut, associated_token::mint = rental_transaction.payment_mint, associated_token::authority = rental_transaction)]
     pub escrow_usdc: Account<'info, TokenAccount>,
+    // Renter's token account of the payment mint, receives the refund
+    #[account(mut, token::mint = rental_transaction.payment_mint, token::authority = renter)]
+    pub renter_usdc: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireRentalRequest<'info> {
+    pub caller: Signer<'info>, // Anyone, once the response deadline has passed
+    /// CHECK: The renter recorded on the rental; receives the refund's rent
+    #[account(mut, address = rental_transaction.renter)]
+    pub renter: UncheckedAccount<'info>,
+    #[account(mut, close = renter, seeds = [b"rental_transaction".as_ref(), rental_transaction.item.as_ref(), rental_transaction.renter.as_ref(), rental_transaction.nonce.to_le_bytes().as_ref()], bump)]
+    pub rental_transaction: Account<'info, RentalTransaction>,
+    // The item's reserved rental windows
+    #[account(mut, seeds = [b"booking_schedule".as_ref(), rental_transaction.item.as_ref()], bump = booking_schedule.bump)]
+    pub booking_schedule: Account<'info, BookingSchedule>,
+    // Promo redeemed at booking, if any; the use is given back
+    #[account(mut, constraint = rental_transaction.promo == Some(promo.key()) @ ErrorCode::PromoNotApplicable)]
+    pub promo: Option<Account<'info, Promo>>,
+    #[account(mut, associated_token::mint = rental_transaction.payment_mint, associated_token::authority = rental_transaction)]
+    pub escrow_usdc: Account<'info, TokenAccount>,
+    // Renter's token account of the payment mint, receives the refund
+    #[account(mut, token::mint = rental_transaction.payment_mint, token::authority = rental_transaction.renter)]
+    pub renter_usdc: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CloseRental<'info> {
+    #[account(mut)]
+    pub renter: Signer<'info>, // Receives the rental account's rent
+    #[account(mut, close = renter, has_one = renter @ ErrorCode::Unauthorized, seeds = [b"rental_transaction".as_ref(), rental_transaction.item.as_ref(), renter.key().as_ref(), rental_transaction.nonce.to_le_bytes().as_ref()], bump)]
+    pub rental_transaction: Account<'info, RentalTransaction>,
+}
+
+#[derive(Accounts)]
+pub struct RateRental<'info> {
+    pub renter: Signer<'info>,
+    #[account(mut, seeds = [b"rental_transaction".as_ref(), item_account.key().as_ref(), renter.key().as_ref(), rental_transaction.nonce.to_le_bytes().as_ref()], bump)]
+    pub rental_transaction: Account<'info, RentalTransaction>,
+    #[account(mut)]
+    pub item_account: Account<'info, ItemAccount>,
+    /// CHECK: PDA that signs CPIs into item_registration; holds no data
+    #[account(seeds = [RENTAL_AUTHORITY_SEED], bump)]
+    pub rental_authority: UncheckedAccount<'info>,
+    pub item_registration_program: Program<'info, ItemRegistrationProgram>,
+}
+
+#[derive(Accounts)]
+pub struct OpenConditionReport<'info> {
+    #[account(mut)]
+    pub reporter: Signer<'info>, // Owner or renter of the rental, pays for the report
+    pub rental_transaction: Account<'info, RentalTransaction>,
+    #[account(init, payer = reporter, space = ConditionReport::LEN, seeds = [b"condition_report".as_ref(), rental_transaction.key().as_ref()], bump)]
+    pub condition_report: Account<'info, ConditionReport>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RecordCondition<'info> {
+    pub reporter: Signer<'info>, // Owner or renter of the rental
+    pub rental_transaction: Account<'info, RentalTransaction>,
+    #[account(mut, seeds = [b"condition_report".as_ref(), rental_transaction.key().as_ref()], bump = condition_report.bump)]
+    pub condition_report: Account<'info, ConditionReport>,
+}
+
+#[derive(Accounts)]
+pub struct StartRental<'info> {
+    #[account(mut, seeds = [b"rental_transaction".as_ref(), rental_transaction.item.as_ref(), rental_transaction.renter.as_ref(), rental_transaction.nonce.to_le_bytes().as_ref()], bump)]
+    pub rental_transaction: Account<'info, RentalTransaction>,
+    #[account(mut, address = rental_transaction.item)]
+    pub item_account: Account<'info, ItemAccount>,
+    #[account(mut)] // Registry the item is listed in, for marketplace statistics
+    pub item_registry: Account<'info, ItemRegistry>,
+    /// CHECK: PDA that signs CPIs into item_registration; holds no data
+    #[account(seeds = [RENTAL_AUTHORITY_SEED], bump)]
+    pub rental_authority: UncheckedAccount<'info>,
+    pub item_registration_program: Program<'info, ItemRegistrationProgram>,
+}
+
+#[derive(Accounts)]
+pub struct RecordReturn<'info> {
+    pub renter: Signer<'info>,
+    #[account(mut, has_one = renter @ ErrorCode::Unauthorized, seeds = [b"rental_transaction".as_ref(), rental_transaction.item.as_ref(), renter.key().as_ref(), rental_transaction.nonce.to_le_bytes().as_ref()], bump)]
+    pub rental_transaction: Account<'info, RentalTransaction>,
+}
+
+#[derive(Accounts)]
+pub struct OpenDispute<'info> {
+    #[account(mut)]
+    pub party: Signer<'info>, // Owner or renter of the rental, pays for the dispute account
+    #[account(mut)]
+    pub rental_transaction: Account<'info, RentalTransaction>,
+    #[account(address = rental_transaction.item)]
+    pub item_account: Account<'info, ItemAccount>,
+    // Config of the item's marketplace, which must have an arbiter
+    #[account(
+        seeds = [b"platform_config".as_ref(), item_account.registry.as_ref()],
+        bump = platform_config.bump,
+        constraint = platform_config.arbiter != Pubkey::default() @ ErrorCode::DisputesDisabled
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+    #[account(init, payer = party, space = Dispute::LEN, seeds = [b"dispute".as_ref(), rental_transaction.key().as_ref()], bump)]
+    pub dispute: Account<'info, Dispute>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitDisputeEvidence<'info> {
+    pub party: Signer<'info>, // Owner or renter of the rental
+    pub rental_transaction: Account<'info, RentalTransaction>,
+    #[account(mut, seeds = [b"dispute".as_ref(), rental_transaction.key().as_ref()], bump = dispute.bump)]
+    pub dispute: Account<'info, Dispute>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveDispute<'info> {
+    pub arbiter: Signer<'info>,
+    #[account(
+        seeds = [b"platform_config".as_ref(), item_account.registry.as_ref()],
+        bump = platform_config.bump,
+        has_one = arbiter @ ErrorCode::Unauthorized
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+    /// CHECK: The renter recorded on the rental; receives the escrow account's rent
+    #[account(mut, address = rental_transaction.renter)]
+    pub renter: UncheckedAccount<'info>,
+    #[account(mut, seeds = [b"rental_transaction".as_ref(), item_account.key().as_ref(), rental_transaction.renter.as_ref(), rental_transaction.nonce.to_le_bytes().as_ref()], bump)]
+    pub rental_transaction: Account<'info, RentalTransaction>,
+    #[account(mut, seeds = [b"dispute".as_ref(), rental_transaction.key().as_ref()], bump = dispute.bump)]
+    pub dispute: Account<'info, Dispute>,
+    #[account(mut)]
+    pub item_account: Account<'info, ItemAccount>,
+    // The item's reserved rental windows
+    #[account(mut, seeds = [b"booking_schedule".as_ref(), item_account.key().as_ref()], bump = booking_schedule.bump)]
+    pub booking_schedule: Account<'info, BookingSchedule>,
+    #[account(mut, associated_token::mint = rental_transaction.payment_mint, associated_token::authority = rental_transaction)]
+    pub escrow_usdc: Account<'info, TokenAccount>,
+    // Token accounts of the payment mint receiving the renter's, owner's and platform's shares
+    #[account(mut, token::mint = rental_transaction.payment_mint, token::authority = rental_transaction.renter)]
+    pub renter_usdc: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = rental_transaction.payment_mint, token::authority = rental_transaction.owner)]
+    pub owner_usdc: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = rental_transaction.payment_mint)]
+    pub system_usdc: Account<'info, TokenAccount>,
+    #[account(mut)] // Registry the item is listed in, for marketplace statistics
+    pub item_registry: Account<'info, ItemRegistry>,
+    /// CHECK: PDA that signs CPIs into item_registration; holds no data
+    #[account(seeds = [RENTAL_AUTHORITY_SEED], bump)]
+    pub rental_authority: UncheckedAccount<'info>,
     pub item_registration_program: Program<'info, ItemRegistrationProgram>,
     pub token_program: Program<'info, Token>,
 }
@@ -304,7 +1646,7 @@ pub enum ErrorCode {
     InvalidRentalState,
     #[msg("Start time cannot be in the past")]
     InvalidStartTime,
-    #[msg("Rental duration must be greater than zero hours")]
+    #[msg("Rental duration must be greater than zero")]
     InvalidRentalDuration,
     #[msg("Item is not available for rental")]
     ItemNotAvailable,
@@ -318,22 +1660,564 @@ pub enum ErrorCode {
     RentalNotYetConcluded,
     #[msg("Invalid system account for platform fee")]
     InvalidSystemAccount,
+    #[msg("Rental has not been completed")]
+    RentalNotCompleted,
+    #[msg("Rental has already been rated")]
+    RentalAlreadyRated,
+    #[msg("Item requires a renter with a valid identity attestation")]
+    RenterNotVerified,
+    #[msg("Only the owner or renter can report on this rental")]
+    UnauthorizedReporter,
+    #[msg("Report URI must be between 1 and 200 characters")]
+    InvalidReportUri,
+    #[msg("Condition has already been recorded for this stage")]
+    ConditionAlreadyRecorded,
+    #[msg("Rentals of items with pricing rules are limited to 744 booking units (31 days of hours)")]
+    RentalTooLongForPricingRules,
+    #[msg("Unauthorized to perform this action")]
+    Unauthorized,
+    #[msg("Promo code must be between 1 and 32 characters")]
+    InvalidPromoCode,
+    #[msg("Invalid promo parameters")]
+    InvalidPromo,
+    #[msg("Promo code has expired")]
+    PromoExpired,
+    #[msg("Promo code has no uses left")]
+    PromoExhausted,
+    #[msg("Promo code does not apply to this item")]
+    PromoNotApplicable,
+    #[msg("Item is on hold for another renter")]
+    ItemOnHold,
+    #[msg("Hold has not expired yet")]
+    HoldNotExpired,
+    #[msg("Item and config belong to different marketplaces")]
+    MarketplaceMismatch,
+    #[msg("Payment mint or its decimals differ from the item's price mint")]
+    PaymentMintMismatch,
+    #[msg("SOL payments are not enabled for this marketplace")]
+    SolPaymentsDisabled,
+    #[msg("Item is not priced in the marketplace's USD mint")]
+    ItemNotUsdPriced,
+    #[msg("Price feed account is invalid")]
+    InvalidPriceFeed,
+    #[msg("Oracle price is unavailable or not positive")]
+    InvalidOraclePrice,
+    #[msg("Oracle price is stale")]
+    StaleOraclePrice,
+    #[msg("Oracle price confidence interval is too wide")]
+    OraclePriceUncertain,
+    #[msg("Amount due exceeds the maximum accepted by the renter")]
+    SlippageExceeded,
+    #[msg("Rental request has expired")]
+    RentalRequestExpired,
+    #[msg("Rental request has not expired yet")]
+    RentalRequestNotExpired,
+    #[msg("Rental is not settled yet")]
+    RentalNotSettled,
+    #[msg("Item is already booked for part of this period")]
+    BookingConflict,
+    #[msg("Item has too many upcoming bookings")]
+    BookingScheduleFull,
+    #[msg("No arbiter is configured for this marketplace")]
+    DisputesDisabled,
+    #[msg("Dispute already holds the maximum number of evidence entries")]
+    DisputeEvidenceFull,
+    #[msg("Shares must be at most 10000 basis points")]
+    InvalidShare,
+    #[msg("Rental has already started")]
+    RentalAlreadyStarted,
+    #[msg("Return has already been recorded")]
+    ReturnAlreadyRecorded,
+    #[msg("Item listing has expired")]
+    ListingExpired,
+    #[msg("Return time must be between the rental start and now")]
+    InvalidReturnTime,
+    #[msg("Rental has not started yet")]
+    RentalNotStarted,
 }
 
-// Helper function for price calculation
-fn calculate_total_price(price_per_hour: u64, price_per_day: u64, hours: u64) -> Result<u64> {
-    let total_hour_price = price_per_hour.checked_mul(hours).ok_or(ErrorCode::ArithmeticOverflow)?;
-    
-    let days = hours / 24;
-    let remaining_hours = hours % 24;
-    
-    let daily_rate_total = price_per_day.checked_mul(days).ok_or(ErrorCode::ArithmeticOverflow)?;
-    let hourly_rate_for_remaining = price_per_hour.checked_mul(remaining_hours).ok_or(ErrorCode::ArithmeticOverflow)?;
-    let total_day_price = daily_rate_total.checked_add(hourly_rate_for_remaining).ok_or(ErrorCode::ArithmeticOverflow)?;
+// Maps errors of the shared pricing crate to this program's errors
+fn pricing_error(error: PricingError) -> Error {
+    match error {
+        PricingError::Overflow => error!(ErrorCode::ArithmeticOverflow),
+        PricingError::TooLongForPricingRules => error!(ErrorCode::RentalTooLongForPricingRules),
+        PricingError::InvalidPrice => error!(ErrorCode::InvalidOraclePrice),
+        PricingError::InvalidShare => error!(ErrorCode::InvalidShare),
+    }
+}
+
+// Reads the aggregate price and exponent of a Pyth v2 price account, rejecting prices that are
+// not trading, stale, non-positive or too uncertain
+fn read_sol_usd_price(feed: &AccountInfo, now: i64) -> Result<(i64, i32)> {
+    let data = feed.try_borrow_data()?;
+    require!(data.len() >= PYTH_AGG_STATUS_OFFSET + 4, ErrorCode::InvalidPriceFeed);
+    let read_u32 = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+    let read_i64 = |offset: usize| i64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+    let read_u64 = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+
+    require!(read_u32(0) == PYTH_MAGIC && read_u32(8) == PYTH_PRICE_ACCOUNT_TYPE, ErrorCode::InvalidPriceFeed);
+    require!(read_u32(PYTH_AGG_STATUS_OFFSET) == PYTH_STATUS_TRADING, ErrorCode::InvalidOraclePrice);
+
+    let expo = read_u32(PYTH_EXPO_OFFSET) as i32;
+    let published_at = read_i64(PYTH_TIMESTAMP_OFFSET);
+    let price = read_i64(PYTH_AGG_PRICE_OFFSET);
+    let confidence = read_u64(PYTH_AGG_CONF_OFFSET);
+
+    require!(now.saturating_sub(published_at) <= MAX_PRICE_AGE_SECONDS, ErrorCode::StaleOraclePrice);
+    require!(price > 0, ErrorCode::InvalidOraclePrice);
+    require!(
+        confidence as u128 * 10_000 <= price as u128 * MAX_PRICE_CONFIDENCE_BPS,
+        ErrorCode::OraclePriceUncertain
+    );
+    Ok((price, expo))
+}
+
+// Accounts of a new booking, shared by initiate_rental and initiate_rental_with_sol, which only
+// differ in how the renter pays
+struct Booking<'a, 'info> {
+    renter: &'a Signer<'info>,
+    owner: &'a UncheckedAccount<'info>,
+    item_account: &'a Account<'info, ItemAccount>,
+    item_registry: &'a Account<'info, ItemRegistry>,
+    rental_transaction: &'a mut Account<'info, RentalTransaction>,
+    booking_schedule: &'a mut Account<'info, BookingSchedule>,
+    booking_schedule_bump: u8,
+    attestation: Option<&'a Attestation>,
+    promo: Option<&'a mut Account<'info, Promo>>,
+    hold: &'a UncheckedAccount<'info>,
+    rental_authority: &'a UncheckedAccount<'info>,
+    rental_authority_bump: u8,
+    item_registration_program: &'a Program<'info, ItemRegistrationProgram>,
+}
+
+impl<'a, 'info> Booking<'a, 'info> {
+    // Validates and records the booking, with its amounts converted from the item's price
+    // currency into `payment_mint` by `convert`. Returns the amount due (price and security
+    // deposit), which the caller escrows.
+    fn book(self, duration: u64, start_time: i64, payment_mint: Pubkey, convert: impl Fn(u64) -> Result<u64>) -> Result<u64> {
+        let item = self.item_account;
+        let end_time = validate_booking(item, &self.renter.key(), self.attestation, duration, start_time)?;
+
+        // Honor any hold on the item: the holder's own booking consumes it, while other renters
+        // cannot book the held slot until the hold expires
+        honor_hold(self.hold, self.renter, start_time, end_time)?;
+
+        // Reserve the rental window in the item's schedule, created by the first booking if the
+        // owner has not opened it; bookings may not overlap
+        self.booking_schedule.open(item.key(), self.booking_schedule_bump);
+        self.booking_schedule.reserve(self.rental_transaction.key(), start_time, end_time)?;
 
-    if total_hour_price > total_day_price {
-        Ok(total_day_price)
+        // Redeem the promo code, if any
+        let promo_key = self.promo.as_ref().map(|promo| promo.key());
+        let promotion = match self.promo {
+            Some(promo) => Some(promo.redeem(item, Clock::get()?.unix_timestamp)?),
+            None => None,
+        };
+        let price = pricing::quote(&item.listing(), duration, start_time, promotion.as_ref()).map_err(pricing_error)?;
+        let total_price = convert(price.total_price)?;
+        let security_deposit = convert(price.security_deposit)?;
+        let amount_due = total_price.checked_add(security_deposit).ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        let rental = self.rental_transaction;
+        rental.nonce = item.next_rental_nonce;
+        rental.item = item.key();
+        rental.renter = self.renter.key();
+        rental.owner = self.owner.key();
+        rental.start_time = start_time;
+        rental.end_time = end_time;
+        rental.total_price = total_price;
+        rental.status = match item.booking_mode {
+            BookingMode::Instant => RentalStatus::Active,
+            BookingMode::RequestToBook => RentalStatus::Requested,
+        };
+        rental.respond_by = respond_by(item.booking_mode, start_time)?;
+        rental.is_rated = false;
+        rental.discount = convert(price.discount)?;
+        rental.platform_fee = convert(price.platform_fee)?;
+        rental.promo = promo_key;
+        rental.security_deposit = security_deposit;
+        rental.payment_mint = payment_mint;
+        rental.cancellation_policy = item.cancellation_policy;
+        rental.late_fee_per_hour = convert(item.late_fee_per_hour)?;
+        rental.late_grace_minutes = item.late_grace_minutes;
+        rental.returned_at = 0;
+        rental.late_fee = 0;
+        rental.started = false;
+
+        // Advance the item's rental nonce so the renter can book the item again later
+        claim_rental_nonce(self.item_registration_program, item, self.rental_authority, self.rental_authority_bump)?;
+
+        // Instant bookings are confirmed right away; requests wait for the owner
+        if item.booking_mode == BookingMode::Instant {
+            confirm_booking(
+                self.item_registration_program,
+                item,
+                self.item_registry,
+                self.rental_authority,
+                self.rental_authority_bump,
+            )?;
+        }
+
+        Ok(amount_due)
+    }
+}
+
+// Booking checks shared by quote_rental and initiate_rental. Returns the rental's end time.
+fn validate_booking(item: &ItemAccount, renter: &Pubkey, attestation: Option<&Attestation>, duration: u64, start_time: i64) -> Result<i64> {
+    let now = Clock::get()?.unix_timestamp;
+    require!(start_time >= now, ErrorCode::InvalidStartTime);
+    require!(duration > 0, ErrorCode::InvalidRentalDuration);
+
+    // Overlapping bookings are checked against the item's BookingSchedule
+    require_listed(item, now)?;
+
+    // Items flagged as requiring verified renters need a valid attestation for the renter
+    if item.requires_verified_renter {
+        let verified = attestation.map_or(false, |a| a.is_valid_for(renter, now));
+        require!(verified, ErrorCode::RenterNotVerified);
+    }
+
+    let duration_seconds = item.listing().duration_seconds(duration).map_err(pricing_error)?;
+    let end_time = start_time.checked_add(duration_seconds).ok_or(ErrorCode::ArithmeticOverflow)?;
+    Ok(end_time)
+}
+
+// Items the owner has marked unavailable, or whose listing has expired, cannot be held or booked,
+// even before the delist_expired_item crank has run
+fn require_listed(item: &ItemAccount, now: i64) -> Result<()> {
+    require!(item.is_available, ErrorCode::ItemNotAvailable);
+    require!(item.expires_at.map_or(true, |expiry| now < expiry), ErrorCode::ListingExpired);
+    Ok(())
+}
+
+// Checks the item's hold PDA allows `renter` to book [start_time, end_time): an unexpired hold by
+// someone else blocks overlapping slots. Returns true if the renter holds the item.
+fn check_hold(hold_info: &AccountInfo, renter: &Pubkey, start_time: i64, end_time: i64) -> Result<bool> {
+    if hold_info.data_is_empty() {
+        return Ok(false);
+    }
+    require_keys_eq!(*hold_info.owner, crate::ID, ErrorCode::InvalidRentalState);
+    let hold = Hold::try_deserialize(&mut &hold_info.try_borrow_data()?[..])?;
+
+    if hold.holder == *renter {
+        return Ok(true);
+    }
+    let overlaps = start_time < hold.end_time && hold.start_time < end_time;
+    require!(Clock::get()?.unix_timestamp >= hold.expires_at || !overlaps, ErrorCode::ItemOnHold);
+    Ok(false)
+}
+
+// Checks the item's hold PDA at booking time. A hold placed by the renter is consumed (its bond
+// and rent are refunded to the renter).
+fn honor_hold<'info>(hold_info: &AccountInfo<'info>, renter: &AccountInfo<'info>, start_time: i64, end_time: i64) -> Result<()> {
+    if check_hold(hold_info, &renter.key(), start_time, end_time)? {
+        close_program_account(hold_info, renter)?;
+    }
+    Ok(())
+}
+
+// Creates a rent-exempt account of `space` bytes owned by this program at the PDA signed for by
+// `seeds`. Like Anchor's init, a PDA that was already sent lamports is topped up, allocated and
+// assigned instead, since create_account fails on an account with a balance.
+fn create_program_account<'info>(
+    payer: &AccountInfo<'info>,
+    info: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space);
+    let program = system_program.to_account_info();
+    if info.lamports() == 0 {
+        return system_program::create_account(
+            CpiContext::new_with_signer(
+                program,
+                system_program::CreateAccount { from: payer.clone(), to: info.clone() },
+                &[seeds],
+            ),
+            rent,
+            space as u64,
+            &crate::ID,
+        );
+    }
+    let top_up = rent.saturating_sub(info.lamports());
+    if top_up > 0 {
+        system_program::transfer(
+            CpiContext::new(program.clone(), system_program::Transfer { from: payer.clone(), to: info.clone() }),
+            top_up,
+        )?;
+    }
+    system_program::allocate(
+        CpiContext::new_with_signer(program.clone(), system_program::Allocate { account_to_allocate: info.clone() }, &[seeds]),
+        space as u64,
+    )?;
+    system_program::assign(
+        CpiContext::new_with_signer(program, system_program::Assign { account_to_assign: info.clone() }, &[seeds]),
+        &crate::ID,
+    )
+}
+
+// Closes an account owned by this program, sending its lamports to `destination`
+fn close_program_account<'info>(info: &AccountInfo<'info>, destination: &AccountInfo<'info>) -> Result<()> {
+    let lamports = info.lamports();
+    **destination.try_borrow_mut_lamports()? = destination.lamports().checked_add(lamports).ok_or(ErrorCode::ArithmeticOverflow)?;
+    **info.try_borrow_mut_lamports()? = 0;
+    info.assign(&system_program::ID);
+    info.realloc(0, false)?;
+    Ok(())
+}
+
+// Rental activity reported for an escrow split: counts as completed with the part of the price
+// that was paid, or as cancelled if the price was refunded in full
+fn settlement_activity(payment_mint: Pubkey, settlement: &Settlement, started: bool) -> RentalActivity {
+    if settlement.price_paid > 0 {
+        RentalActivity::Completed { mint: payment_mint, amount: settlement.price_paid, started }
     } else {
-        Ok(total_hour_price)
+        RentalActivity::Cancelled { started }
+    }
+}
+
+fn emit_settlement(rental: &Account<RentalTransaction>) {
+    emit!(RentalSettled {
+        rental: rental.key(),
+        item: rental.item,
+        renter: rental.renter,
+        owner: rental.owner,
+        nonce: rental.nonce,
+        start_time: rental.start_time,
+        end_time: rental.end_time,
+        status: rental.status,
+        payment_mint: rental.payment_mint,
+        total_price: rental.total_price,
+        discount: rental.discount,
+        platform_fee: rental.platform_fee,
+        security_deposit: rental.security_deposit,
+        late_fee: rental.late_fee,
+        promo: rental.promo,
+        is_rated: rental.is_rated,
+    });
+}
+
+// Response deadline of a new booking: requests must be answered within REQUEST_RESPONSE_SECONDS
+// and before the rental starts
+fn respond_by(booking_mode: BookingMode, start_time: i64) -> Result<i64> {
+    match booking_mode {
+        BookingMode::Instant => Ok(0),
+        BookingMode::RequestToBook => {
+            let deadline = Clock::get()?.unix_timestamp.checked_add(REQUEST_RESPONSE_SECONDS).ok_or(ErrorCode::ArithmeticOverflow)?;
+            Ok(deadline.min(start_time))
+        }
+    }
+}
+
+// Refunds the whole escrow (price and deposit) to the renter and closes it, sending its rent to
+// the renter
+fn refund_escrow<'info>(
+    rental_transaction: &Account<'info, RentalTransaction>,
+    rental_transaction_bump: u8,
+    escrow: &Account<'info, TokenAccount>,
+    renter_token_account: &Account<'info, TokenAccount>,
+    renter: &AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+) -> Result<()> {
+    let refund_amount = rental_transaction.total_price
+        .checked_add(rental_transaction.security_deposit)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    settle_escrow(
+        rental_transaction,
+        rental_transaction_bump,
+        escrow,
+        &[(renter_token_account, refund_amount)],
+        renter,
+        token_program,
+    )
+}
+
+// Pays out the escrow to the given token accounts (skipping zero amounts) and closes it, sending
+// its rent to `rent_destination`. Signed by the rental_transaction PDA, which owns the escrow.
+fn settle_escrow<'info>(
+    rental_transaction: &Account<'info, RentalTransaction>,
+    rental_transaction_bump: u8,
+    escrow: &Account<'info, TokenAccount>,
+    payouts: &[(&Account<'info, TokenAccount>, u64)],
+    rent_destination: &AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+) -> Result<()> {
+    let nonce = rental_transaction.nonce.to_le_bytes();
+    let bump = [rental_transaction_bump];
+    let seeds: &[&[u8]] = &[
+        b"rental_transaction".as_ref(),
+        rental_transaction.item.as_ref(),
+        rental_transaction.renter.as_ref(),
+        &nonce,
+        &bump,
+    ];
+    let signer_seeds = &[seeds];
+
+    for (destination, amount) in payouts.iter().filter(|(_, amount)| *amount > 0) {
+        token::transfer(
+            CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                token::Transfer {
+                    from: escrow.to_account_info(),
+                    to: destination.to_account_info(),
+                    authority: rental_transaction.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            *amount,
+        )?;
+    }
+    token::close_account(CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        token::CloseAccount {
+            account: escrow.to_account_info(),
+            destination: rent_destination.clone(),
+            authority: rental_transaction.to_account_info(),
+        },
+        signer_seeds,
+    ))
+}
+
+// Reports a confirmed booking (counted until it is settled, even while it has not started yet),
+// signed by the rental authority PDA
+fn confirm_booking<'info>(
+    item_registration_program: &Program<'info, ItemRegistrationProgram>,
+    item_account: &Account<'info, ItemAccount>,
+    item_registry: &Account<'info, ItemRegistry>,
+    rental_authority: &UncheckedAccount<'info>,
+    rental_authority_bump: u8,
+) -> Result<()> {
+    record_rental_activity(
+        item_registration_program,
+        item_account,
+        item_registry,
+        rental_authority,
+        rental_authority_bump,
+        RentalActivity::Confirmed,
+    )
+}
+
+// Helper function for advancing the item's rental nonce, signed by the rental authority PDA
+fn claim_rental_nonce<'info>(
+    item_registration_program: &Program<'info, ItemRegistrationProgram>,
+    item_account: &Account<'info, ItemAccount>,
+    rental_authority: &UncheckedAccount<'info>,
+    rental_authority_bump: u8,
+) -> Result<()> {
+    let bump = [rental_authority_bump];
+    let seeds: &[&[u8]] = &[RENTAL_AUTHORITY_SEED, &bump];
+    let signer_seeds = &[seeds];
+    let cpi_accounts = ItemRegClaimRentalNonceAccounts {
+        item_account: item_account.to_account_info(),
+        rental_authority: rental_authority.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(item_registration_program.to_account_info(), cpi_accounts, signer_seeds);
+    item_reg_claim_rental_nonce(cpi_ctx)
+}
+
+// Helper function for reporting rental activity to item_registration, signed by the rental authority PDA
+fn record_rental_activity<'info>(
+    item_registration_program: &Program<'info, ItemRegistrationProgram>,
+    item_account: &Account<'info, ItemAccount>,
+    item_registry: &Account<'info, ItemRegistry>,
+    rental_authority: &UncheckedAccount<'info>,
+    rental_authority_bump: u8,
+    activity: RentalActivity,
+) -> Result<()> {
+    let bump = [rental_authority_bump];
+    let seeds: &[&[u8]] = &[RENTAL_AUTHORITY_SEED, &bump];
+    let signer_seeds = &[seeds];
+    let cpi_accounts = ItemRegRecordRentalActivityAccounts {
+        item_account: item_account.to_account_info(),
+        item_registry: item_registry.to_account_info(),
+        rental_authority: rental_authority.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(item_registration_program.to_account_info(), cpi_accounts, signer_seeds);
+    item_reg_record_rental_activity(cpi_ctx, activity)
+}
+
+// Helper function for incrementing a participant's profile counters, signed by the rental authority PDA.
+// Profiles are optional, so a profile PDA that was never initialized is skipped; an initialized one
+// can only have been created by item_registration, which checks it in the CPI.
+fn record_profile_rental<'info>(
+    item_registration_program: &Program<'info, ItemRegistrationProgram>,
+    user_profile: &UncheckedAccount<'info>,
+    rental_authority: &UncheckedAccount<'info>,
+    rental_authority_bump: u8,
+    role: ProfileRole,
+) -> Result<()> {
+    if user_profile.data_is_empty() {
+        return Ok(());
     }
-}
\ No newline at end of file
+    let bump = [rental_authority_bump];
+    let seeds: &[&[u8]] = &[RENTAL_AUTHORITY_SEED, &bump];
+    let signer_seeds = &[seeds];
+    let cpi_accounts = ItemRegRecordProfileRentalAccounts {
+        user_profile: user_profile.to_account_info(),
+        rental_authority: rental_authority.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(item_registration_program.to_account_info(), cpi_accounts, signer_seeds);
+    item_reg_record_profile_rental(cpi_ctx, role)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule() -> BookingSchedule {
+        BookingSchedule { item: Pubkey::new_unique(), bookings: Vec::new(), bump: 255 }
+    }
+
+    #[test]
+    fn bookings_may_not_overlap() {
+        let mut schedule = schedule();
+        schedule.reserve(Pubkey::new_unique(), 100, 200).unwrap();
+
+        for (start, end) in [(50, 101), (199, 300), (120, 150), (0, 1_000), (100, 200)] {
+            assert_eq!(schedule.reserve(Pubkey::new_unique(), start, end), Err(ErrorCode::BookingConflict.into()));
+        }
+        assert_eq!(schedule.bookings.len(), 1);
+    }
+
+    #[test]
+    fn adjacent_bookings_are_allowed() {
+        let mut schedule = schedule();
+        schedule.reserve(Pubkey::new_unique(), 100, 200).unwrap();
+        schedule.reserve(Pubkey::new_unique(), 200, 300).unwrap();
+        schedule.reserve(Pubkey::new_unique(), 0, 100).unwrap();
+        assert_eq!(schedule.bookings.len(), 3);
+    }
+
+    #[test]
+    fn released_windows_can_be_booked_again() {
+        let mut schedule = schedule();
+        let rental = Pubkey::new_unique();
+        schedule.reserve(rental, 100, 200).unwrap();
+        schedule.release(&rental);
+        schedule.reserve(Pubkey::new_unique(), 150, 250).unwrap();
+        assert_eq!(schedule.bookings.len(), 1);
+    }
+
+    #[test]
+    fn schedule_holds_at_most_max_scheduled_bookings() {
+        let mut schedule = schedule();
+        for index in 0..MAX_SCHEDULED_BOOKINGS as i64 {
+            schedule.reserve(Pubkey::new_unique(), index * 10, index * 10 + 10).unwrap();
+        }
+        let start = MAX_SCHEDULED_BOOKINGS as i64 * 10;
+        assert_eq!(schedule.reserve(Pubkey::new_unique(), start, start + 10), Err(ErrorCode::BookingScheduleFull.into()));
+    }
+
+    #[test]
+    fn open_keeps_an_existing_schedule() {
+        let mut schedule = BookingSchedule { item: Pubkey::default(), bookings: Vec::new(), bump: 0 };
+        let item = Pubkey::new_unique();
+        schedule.open(item, 254);
+        schedule.reserve(Pubkey::new_unique(), 0, 10).unwrap();
+        schedule.open(Pubkey::new_unique(), 1);
+        assert_eq!(schedule.item, item);
+        assert_eq!(schedule.bump, 254);
+        assert_eq!(schedule.bookings.len(), 1);
+    }
+}